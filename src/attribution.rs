@@ -0,0 +1,165 @@
+use crate::{player, team::Team, GameState};
+use avian3d::prelude::*;
+use bevy::prelude::*;
+
+// how long a hit still counts as the reason a goat died
+pub const KNOCKOUT_WINDOW_SECS: f32 = 5.;
+// hits that change a goats speed by less than this dont count, so just leaning on each other doesnt steal knockouts or add damage
+const MIN_HIT_SPEED: f32 = 3.;
+// blasts fall off with distance and reach every goat on the map, this is about 70 away from one
+const MIN_BLAST_SPEED: f32 = 20.;
+
+pub struct AttributionPlugin;
+
+impl Plugin for AttributionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Hit>().add_systems(
+            FixedUpdate,
+            (detect_rams, record_hits)
+                .chain()
+                .before(player::kill_players)
+                .run_if(in_state(GameState::Spectating)),
+        );
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Attacker {
+    Goat { name: String, team: Option<Team> },
+    // the streamers gun
+    Streamer,
+}
+
+/// something pushed a goat around
+#[derive(Event, Clone, Debug)]
+pub struct Hit {
+    pub victim: Entity,
+    pub attacker: Attacker,
+    // how much speed the victim got from it
+    pub strength: f32,
+}
+
+impl Hit {
+    /// whether its hard enough to get the knockout and count as damage
+    pub fn counts(&self) -> bool {
+        let min = match self.attacker {
+            Attacker::Goat { .. } => MIN_HIT_SPEED,
+            Attacker::Streamer => MIN_BLAST_SPEED,
+        };
+        self.strength >= min
+    }
+}
+
+/// the last meaningful hit on a goat, read by kill_players
+#[derive(Component, Clone, Debug)]
+pub struct LastHit {
+    pub attacker: Attacker,
+    pub time: f32,
+}
+
+impl LastHit {
+    pub fn is_recent(&self, now: f32) -> bool {
+        now - self.time < KNOCKOUT_WINDOW_SECS
+    }
+}
+
+// whichever goat was moving into the other one more did the hitting
+fn detect_rams(
+    collisions: Collisions,
+    mut hit_events: EventWriter<Hit>,
+    player_query: Query<(
+        &Transform,
+        &LinearVelocity,
+        &ComputedMass,
+        &player::Player,
+        Option<&Team>,
+    )>,
+) {
+    for contacts in collisions.iter() {
+        if !contacts.is_touching() {
+            continue;
+        }
+        let (Ok((trans1, linvel1)), Ok((trans2, linvel2))) = (
+            player_query
+                .get(contacts.collider1)
+                .map(|goat| (goat.0, goat.1)),
+            player_query
+                .get(contacts.collider2)
+                .map(|goat| (goat.0, goat.1)),
+        ) else {
+            continue;
+        };
+
+        let dir = (trans2.translation - trans1.translation).normalize_or_zero();
+        let (hitter, victim) = if linvel1.0.dot(dir) >= -linvel2.0.dot(dir) {
+            (contacts.collider1, contacts.collider2)
+        } else {
+            (contacts.collider2, contacts.collider1)
+        };
+        let (Ok((_, _, _, hitter_player, hitter_team)), Ok((_, _, victim_mass, _, _))) =
+            (player_query.get(hitter), player_query.get(victim))
+        else {
+            continue;
+        };
+
+        hit_events.write(Hit {
+            victim,
+            attacker: Attacker::Goat {
+                name: hitter_player.name().to_owned(),
+                team: hitter_team.copied(),
+            },
+            strength: contacts.total_normal_impulse_magnitude() / victim_mass.value().max(1.),
+        });
+    }
+}
+
+fn record_hits(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut hit_events: EventReader<Hit>,
+    player_query: Query<(), With<player::Player>>,
+) {
+    for hit in hit_events.read() {
+        if !hit.counts() || !player_query.contains(hit.victim) {
+            continue;
+        }
+        commands.entity(hit.victim).insert(LastHit {
+            attacker: hit.attacker.clone(),
+            time: time.elapsed_secs(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(attacker: Attacker, strength: f32) -> Hit {
+        Hit {
+            victim: Entity::PLACEHOLDER,
+            attacker,
+            strength,
+        }
+    }
+
+    #[test]
+    fn weak_hits_dont_count() {
+        let goat = || Attacker::Goat {
+            name: "someone".to_owned(),
+            team: None,
+        };
+        assert!(!hit(goat(), 1.).counts());
+        assert!(hit(goat(), MIN_HIT_SPEED).counts());
+        assert!(hit(goat(), 50.).counts());
+    }
+
+    #[test]
+    fn far_away_blasts_dont_count() {
+        // what gun::explode gives a goat this far from the blast
+        let blast = |distance: f32| hit(Attacker::Streamer, 1e5 / (distance * distance));
+        assert!(!blast(150.).counts());
+        assert!(!blast(80.).counts());
+        assert!(blast(50.).counts());
+        assert!(blast(5.).counts());
+    }
+}
@@ -0,0 +1,308 @@
+use crate::{menu, player, twitch, GameState};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+// how long a goat waits for a command before the ai takes over again
+const AI_FALLBACK_SECS: f32 = 10.;
+// how long one l or r keeps the goat turning
+const STEER_SECS: f32 = 0.5;
+
+pub struct ChatControlPlugin;
+
+impl Plugin for ChatControlPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Democracy::default())
+            .add_systems(OnEnter(GameState::Spectating), attach_chat_control)
+            .add_systems(
+                Update,
+                (
+                    read_owner_commands.run_if(control_mode_is(ControlMode::Owner)),
+                    (pick_democracy_goat, count_votes)
+                        .chain()
+                        .run_if(control_mode_is(ControlMode::Democracy)),
+                )
+                    .run_if(in_state(GameState::Spectating)),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum ControlMode {
+    #[default]
+    Ai,
+    Owner,
+    Democracy,
+}
+
+impl ControlMode {
+    pub fn next(self) -> Self {
+        match self {
+            ControlMode::Ai => ControlMode::Owner,
+            ControlMode::Owner => ControlMode::Democracy,
+            ControlMode::Democracy => ControlMode::Ai,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ControlMode::Ai => "off",
+            ControlMode::Owner => "owners",
+            ControlMode::Democracy => "democracy",
+        }
+    }
+}
+
+fn control_mode_is(mode: ControlMode) -> impl Fn(Res<menu::Settings>) -> bool {
+    move |settings: Res<menu::Settings>| settings.control_mode == mode
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum ChatCommand {
+    Left,
+    Right,
+    Go,
+    Stop,
+    Ram,
+}
+
+impl ChatCommand {
+    fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "l" | "left" => Some(ChatCommand::Left),
+            "r" | "right" => Some(ChatCommand::Right),
+            "go" => Some(ChatCommand::Go),
+            "stop" => Some(ChatCommand::Stop),
+            "ram" => Some(ChatCommand::Ram),
+            _ => None,
+        }
+    }
+}
+
+pub struct DriveInput {
+    // -1 is left, 1 is right
+    pub steer: f32,
+    pub throttle: bool,
+    pub ram: bool,
+}
+
+/// goat that listens to chat instead of the ai while someone is giving it commands
+#[derive(Component)]
+pub struct ChatControlled {
+    steer: f32,
+    steer_until: f32,
+    throttle: bool,
+    ram: bool,
+    last_input: f32,
+}
+
+impl Default for ChatControlled {
+    fn default() -> Self {
+        ChatControlled {
+            steer: 0.,
+            steer_until: 0.,
+            throttle: true,
+            ram: false,
+            last_input: f32::NEG_INFINITY,
+        }
+    }
+}
+
+impl ChatControlled {
+    fn apply(&mut self, command: ChatCommand, now: f32) {
+        match command {
+            ChatCommand::Left => {
+                self.steer = -1.;
+                self.steer_until = now + STEER_SECS;
+            }
+            ChatCommand::Right => {
+                self.steer = 1.;
+                self.steer_until = now + STEER_SECS;
+            }
+            ChatCommand::Go => self.throttle = true,
+            ChatCommand::Stop => self.throttle = false,
+            ChatCommand::Ram => self.ram = true,
+        }
+        self.last_input = now;
+    }
+
+    /// returns none when chat has been quiet for too long and the ai should drive
+    pub fn take_input(&mut self, now: f32) -> Option<DriveInput> {
+        if now - self.last_input > AI_FALLBACK_SECS {
+            return None;
+        }
+
+        let steer = if now < self.steer_until {
+            self.steer
+        } else {
+            0.
+        };
+        let ram = self.ram;
+        self.ram = false;
+
+        Some(DriveInput {
+            steer,
+            throttle: self.throttle,
+            ram,
+        })
+    }
+}
+
+#[derive(Resource, Default)]
+struct Democracy {
+    goat: Option<Entity>,
+    // in the order each command got its first vote
+    votes: Vec<(ChatCommand, u32)>,
+    voters: HashSet<String>,
+    timer: Timer,
+}
+
+fn attach_chat_control(
+    mut commands: Commands,
+    settings: Res<menu::Settings>,
+    mut democracy: ResMut<Democracy>,
+    player_query: Query<Entity, With<player::Player>>,
+) {
+    match settings.control_mode {
+        ControlMode::Ai => {}
+        ControlMode::Owner => {
+            for entity in &player_query {
+                commands.entity(entity).insert(ChatControlled::default());
+            }
+        }
+        ControlMode::Democracy => {
+            *democracy = Democracy {
+                timer: Timer::from_seconds(settings.democracy_interval, TimerMode::Repeating),
+                ..Default::default()
+            };
+        }
+    }
+}
+
+fn read_owner_commands(
+    mut events: EventReader<twitch::ChatMessage>,
+    time: Res<Time>,
+    mut player_query: Query<(&player::Player, &mut ChatControlled)>,
+) {
+    for event in events.read() {
+        let Some(command) = ChatCommand::parse(&event.0.text) else {
+            continue;
+        };
+
+        for (player, mut control) in &mut player_query {
+            if player.is_owned_by(&event.0.sender) {
+                control.apply(command, time.elapsed_secs());
+            }
+        }
+    }
+}
+
+// picks a new goat for chat whenever the old one is gone
+fn pick_democracy_goat(
+    mut commands: Commands,
+    mut democracy: ResMut<Democracy>,
+    player_query: Query<Entity, With<player::Player>>,
+) {
+    if democracy
+        .goat
+        .is_some_and(|goat| player_query.contains(goat))
+    {
+        return;
+    }
+
+    let players = player_query.iter().collect::<Vec<_>>();
+    if players.is_empty() {
+        democracy.goat = None;
+        return;
+    }
+
+    let goat = players[rand::random_range(0..players.len())];
+    commands.entity(goat).insert(ChatControlled::default());
+    democracy.goat = Some(goat);
+}
+
+// a tie goes to whichever command got voted for first
+fn most_votes(votes: &[(ChatCommand, u32)]) -> Option<ChatCommand> {
+    // max_by_key keeps the last of equal ones so go through them backwards
+    votes
+        .iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(command, _)| *command)
+}
+
+fn count_votes(
+    mut events: EventReader<twitch::ChatMessage>,
+    time: Res<Time>,
+    mut democracy: ResMut<Democracy>,
+    mut control_query: Query<&mut ChatControlled>,
+) {
+    for event in events.read() {
+        let Some(command) = ChatCommand::parse(&event.0.text) else {
+            continue;
+        };
+        if !democracy.voters.insert(event.0.sender.clone()) {
+            continue;
+        }
+        match democracy
+            .votes
+            .iter_mut()
+            .find(|(voted, _)| *voted == command)
+        {
+            Some((_, count)) => *count += 1,
+            None => democracy.votes.push((command, 1)),
+        }
+    }
+
+    democracy.timer.tick(time.delta());
+    if !democracy.timer.just_finished() {
+        return;
+    }
+
+    let winner = most_votes(&democracy.votes);
+    democracy.votes.clear();
+    democracy.voters.clear();
+
+    let (Some(command), Some(goat)) = (winner, democracy.goat) else {
+        return;
+    };
+    if let Ok(mut control) = control_query.get_mut(goat) {
+        control.apply(command, time.elapsed_secs());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_parse() {
+        assert_eq!(ChatCommand::parse("l"), Some(ChatCommand::Left));
+        assert_eq!(ChatCommand::parse("LEFT"), Some(ChatCommand::Left));
+        assert_eq!(ChatCommand::parse(" r "), Some(ChatCommand::Right));
+        assert_eq!(ChatCommand::parse("right"), Some(ChatCommand::Right));
+        assert_eq!(ChatCommand::parse("Go"), Some(ChatCommand::Go));
+        assert_eq!(ChatCommand::parse("stop"), Some(ChatCommand::Stop));
+        assert_eq!(ChatCommand::parse("ram"), Some(ChatCommand::Ram));
+        assert_eq!(ChatCommand::parse("go left"), None);
+        assert_eq!(ChatCommand::parse("!play"), None);
+        assert_eq!(ChatCommand::parse(""), None);
+    }
+
+    #[test]
+    fn ties_go_to_the_first_vote() {
+        assert_eq!(most_votes(&[]), None);
+        assert_eq!(
+            most_votes(&[(ChatCommand::Ram, 2), (ChatCommand::Left, 3)]),
+            Some(ChatCommand::Left)
+        );
+        assert_eq!(
+            most_votes(&[
+                (ChatCommand::Stop, 1),
+                (ChatCommand::Right, 2),
+                (ChatCommand::Left, 2),
+            ]),
+            Some(ChatCommand::Right)
+        );
+    }
+}
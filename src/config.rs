@@ -0,0 +1,562 @@
+use crate::{
+    chat_control::ControlMode,
+    game_mode::GameMode,
+    goat_stats::StatsMode,
+    hazard::{default_layout, HazardLayout, HazardSpawn},
+    lobby::JoinSelection,
+    menu::{ChannelName, Settings},
+    moderation::{NameFilter, DEFAULT_BLOCKED_NAMES},
+    player::GoatSpeed,
+    redeem::{Cheer, Redemptions, Reward},
+    respawn::RespawnMode,
+    spawn::{default_zones, SpawnFacing, SpawnLayout, SpawnZone, SpawnZones},
+    spectator_camera::FreeFly,
+    team::TeamMode,
+};
+use avian3d::prelude::*;
+use bevy::audio::Volume;
+use bevy::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_PATH: &str = "config.ron";
+// env vars are this plus the field name in caps, like LIVESTOCK_CHANNEL_NAME
+const ENV_PREFIX: &str = "LIVESTOCK_";
+
+pub struct ConfigPlugin;
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut App) {
+        let (path, file, config) = Config::load_with_overrides();
+        app.insert_resource(ConfigPath(path))
+            .insert_resource(FileConfig(file))
+            .insert_resource(config)
+            .add_event::<SaveConfig>()
+            .add_systems(Startup, apply_config)
+            .add_systems(Update, save_config);
+    }
+}
+
+/// everything that survives a restart, in config.ron next to the game.
+/// the resource has env vars and flags applied on top, those never get saved
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Config {
+    pub channel_name: String,
+    pub filter_joins: bool,
+    pub goats_per_player: u32,
+    pub control_mode: ControlMode,
+    pub democracy_interval: f32,
+    pub team_mode: TeamMode,
+    pub game_mode: GameMode,
+    pub hazards: bool,
+    pub max_goats: Option<u32>,
+    pub auto_start_goats: Option<u32>,
+    pub auto_start_secs: Option<f32>,
+    pub join_selection: JoinSelection,
+    pub sub_priority: bool,
+    pub late_joins: bool,
+    pub respawn: RespawnMode,
+    pub respawn_secs: f32,
+    pub respawn_round_secs: f32,
+    pub spawn_layout: SpawnLayout,
+    pub spawn_facing: SpawnFacing,
+    pub spawn_margin: f32,
+    pub spawn_spacing: f32,
+    pub spawn_zones: Vec<SpawnZone>,
+    pub hazard_layout: Vec<HazardSpawn>,
+    pub goat_stats: StatsMode,
+    pub gravity: f32,
+    pub min_goat_speed: f32,
+    pub max_goat_speed: f32,
+    pub camera_speed: f32,
+    // 0 to 1
+    pub volume: f32,
+    // regexes, chatters with a matching name show up as goat and a number
+    pub blocked_names: Vec<String>,
+    // channel point rewards by id and cheers by bits, and what they do in the game
+    pub rewards: Vec<Reward>,
+    pub cheers: Vec<Cheer>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let settings = Settings::default();
+        Config {
+            channel_name: String::new(),
+            filter_joins: settings.filter_joins,
+            goats_per_player: settings.goats_per_player,
+            control_mode: settings.control_mode,
+            democracy_interval: settings.democracy_interval,
+            team_mode: settings.team_mode,
+            game_mode: settings.game_mode,
+            hazards: settings.hazards,
+            max_goats: settings.max_goats,
+            auto_start_goats: settings.auto_start_goats,
+            auto_start_secs: settings.auto_start_secs,
+            join_selection: settings.join_selection,
+            sub_priority: settings.sub_priority,
+            late_joins: settings.late_joins,
+            respawn: settings.respawn,
+            respawn_secs: settings.respawn_secs,
+            respawn_round_secs: settings.respawn_round_secs,
+            spawn_layout: settings.spawn_layout,
+            spawn_facing: settings.spawn_facing,
+            spawn_margin: settings.spawn_margin,
+            spawn_spacing: settings.spawn_spacing,
+            spawn_zones: default_zones(),
+            hazard_layout: default_layout(),
+            goat_stats: settings.goat_stats,
+            gravity: 50.,
+            min_goat_speed: GoatSpeed::default().min,
+            max_goat_speed: GoatSpeed::default().max,
+            camera_speed: FreeFly::default().speed,
+            volume: 1.,
+            blocked_names: DEFAULT_BLOCKED_NAMES
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            rewards: vec![],
+            cheers: vec![],
+        }
+    }
+}
+
+/// asks for the current settings to be written to the config file
+#[derive(Event)]
+pub struct SaveConfig;
+
+#[derive(Resource)]
+struct ConfigPath(PathBuf);
+
+// the config as it is in the file, without env vars or flags
+#[derive(Resource)]
+struct FileConfig(Config);
+
+fn parse<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    ron::from_str(value).map_err(|err| err.to_string())
+}
+
+// takes 20, Some(20) or none
+fn parse_optional<T: DeserializeOwned>(value: &str) -> Result<Option<T>, String> {
+    if value.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+    parse::<T>(value).map(Some).or_else(|_| parse(value))
+}
+
+impl Config {
+    // the file first, then env vars, then command line flags. gives back the file too so saving can leave the rest out
+    fn load_with_overrides() -> (PathBuf, Config, Config) {
+        let args = std::env::args().skip(1).collect::<Vec<_>>();
+        let flags = parse_flags(&args);
+
+        let path = flags
+            .iter()
+            .find(|(key, _)| key == "config")
+            .map(|(_, value)| value.clone())
+            .or_else(|| std::env::var(ENV_PREFIX.to_owned() + "CONFIG").ok())
+            .unwrap_or_else(|| DEFAULT_PATH.to_owned());
+        let path = PathBuf::from(path);
+
+        let file = Config::load(&path);
+        let mut config = file.clone();
+        for (key, value) in std::env::vars() {
+            let Some(field) = key.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let field = field.to_lowercase();
+            if field == "config" {
+                continue;
+            }
+            if let Err(err) = config.set(&field, &value) {
+                error!("bad env var {key}: {err}");
+            }
+        }
+        for (field, value) in &flags {
+            if field == "config" {
+                continue;
+            }
+            if let Err(err) = config.set(field, value) {
+                error!("bad flag --{field}: {err}");
+            }
+        }
+
+        if config.max_goat_speed <= config.min_goat_speed {
+            error!("max_goat_speed has to be more than min_goat_speed");
+            config.max_goat_speed = config.min_goat_speed + 1.;
+        }
+        (path, file, config)
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return Config::default();
+        };
+        ron::from_str(&text).unwrap_or_else(|err| {
+            error!("couldnt read {}: {err}", path.display());
+            Config::default()
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())?;
+        std::fs::write(path, text).map_err(|err| err.to_string())
+    }
+
+    /// changes one field by name, the value is written like in the config file
+    pub fn set(&mut self, field: &str, value: &str) -> Result<(), String> {
+        match field.replace('-', "_").as_str() {
+            // a bare name is easier to type than a quoted ron string
+            "channel_name" => self.channel_name = value.trim_matches('"').to_lowercase(),
+            "filter_joins" => self.filter_joins = parse(value)?,
+            "goats_per_player" => self.goats_per_player = parse::<u32>(value)?.max(1),
+            "control_mode" => self.control_mode = parse(value)?,
+            "democracy_interval" => self.democracy_interval = parse(value)?,
+            "team_mode" => self.team_mode = parse(value)?,
+            "game_mode" => self.game_mode = parse(value)?,
+            "hazards" => self.hazards = parse(value)?,
+            "max_goats" => self.max_goats = parse_optional(value)?,
+            "auto_start_goats" => self.auto_start_goats = parse_optional(value)?,
+            "auto_start_secs" => self.auto_start_secs = parse_optional(value)?,
+            "join_selection" => self.join_selection = parse(value)?,
+            "sub_priority" => self.sub_priority = parse(value)?,
+            "late_joins" => self.late_joins = parse(value)?,
+            "respawn" => self.respawn = parse(value)?,
+            "respawn_secs" => self.respawn_secs = parse(value)?,
+            "respawn_round_secs" => self.respawn_round_secs = parse(value)?,
+            "spawn_layout" => self.spawn_layout = parse(value)?,
+            "spawn_facing" => self.spawn_facing = parse(value)?,
+            // the field is 600 across, a bigger margin leaves nowhere to stand
+            "spawn_margin" => self.spawn_margin = parse::<f32>(value)?.clamp(0., 250.),
+            "spawn_spacing" => self.spawn_spacing = parse::<f32>(value)?.max(5.),
+            "spawn_zones" => self.spawn_zones = parse(value)?,
+            "hazard_layout" => self.hazard_layout = parse(value)?,
+            "goat_stats" => self.goat_stats = parse(value)?,
+            "gravity" => self.gravity = parse(value)?,
+            "min_goat_speed" => self.min_goat_speed = parse(value)?,
+            "max_goat_speed" => self.max_goat_speed = parse(value)?,
+            "camera_speed" => self.camera_speed = parse(value)?,
+            "volume" => self.volume = parse::<f32>(value)?.clamp(0., 1.),
+            "blocked_names" => self.blocked_names = parse(value)?,
+            "rewards" => self.rewards = parse(value)?,
+            "cheers" => self.cheers = parse(value)?,
+            _ => return Err(format!("theres no setting called {field}")),
+        }
+        Ok(())
+    }
+}
+
+// --name value and --name=value, a flag without a value means true
+fn parse_flags(args: &[String]) -> Vec<(String, String)> {
+    let mut flags = vec![];
+    let mut i = 0;
+    while i < args.len() {
+        let Some(flag) = args[i].strip_prefix("--") else {
+            error!("dont know what to do with {}", args[i]);
+            i += 1;
+            continue;
+        };
+        match flag.split_once('=') {
+            Some((key, value)) => flags.push((key.to_owned(), value.to_owned())),
+            None => match args.get(i + 1).filter(|next| !next.starts_with("--")) {
+                Some(value) => {
+                    flags.push((flag.to_owned(), value.clone()));
+                    i += 1;
+                }
+                None => flags.push((flag.to_owned(), "true".to_owned())),
+            },
+        }
+        i += 1;
+    }
+    flags
+}
+
+fn apply_config(
+    config: Res<Config>,
+    mut channel_name: ResMut<ChannelName>,
+    mut settings: ResMut<Settings>,
+    mut gravity: ResMut<Gravity>,
+    mut goat_speed: ResMut<GoatSpeed>,
+    mut free_fly: ResMut<FreeFly>,
+    mut global_volume: ResMut<GlobalVolume>,
+    mut name_filter: ResMut<NameFilter>,
+    mut spawn_zones: ResMut<SpawnZones>,
+    mut hazard_layout: ResMut<HazardLayout>,
+    mut redemptions: ResMut<Redemptions>,
+) {
+    channel_name.0 = config.channel_name.clone();
+    *settings = Settings {
+        filter_joins: config.filter_joins,
+        // nobody could join with 0
+        goats_per_player: config.goats_per_player.max(1),
+        control_mode: config.control_mode,
+        democracy_interval: config.democracy_interval,
+        team_mode: config.team_mode,
+        game_mode: config.game_mode,
+        hazards: config.hazards,
+        max_goats: config.max_goats,
+        auto_start_goats: config.auto_start_goats,
+        auto_start_secs: config.auto_start_secs,
+        join_selection: config.join_selection,
+        sub_priority: config.sub_priority,
+        late_joins: config.late_joins,
+        respawn: config.respawn,
+        respawn_secs: config.respawn_secs,
+        respawn_round_secs: config.respawn_round_secs,
+        spawn_layout: config.spawn_layout,
+        spawn_facing: config.spawn_facing,
+        spawn_margin: config.spawn_margin.clamp(0., 250.),
+        spawn_spacing: config.spawn_spacing.max(5.),
+        goat_stats: config.goat_stats,
+    };
+    gravity.0 = Vec3::NEG_Y * config.gravity;
+    *goat_speed = GoatSpeed {
+        min: config.min_goat_speed,
+        max: config.max_goat_speed,
+    };
+    free_fly.speed = config.camera_speed;
+    global_volume.volume = Volume::Linear(config.volume);
+    *name_filter = NameFilter::new(&config.blocked_names);
+    spawn_zones.0 = config.spawn_zones.clone();
+    hazard_layout.0 = config.hazard_layout.clone();
+    *redemptions = Redemptions {
+        rewards: config.rewards.clone(),
+        cheers: config.cheers.clone(),
+    };
+}
+
+// only what the menu changed goes over what was in the file, so env vars and flags stay temporary
+fn keep_change<T: PartialEq + Clone>(file: &mut T, loaded: &mut T, menu: T) {
+    if *loaded != menu {
+        *file = menu.clone();
+        *loaded = menu;
+    }
+}
+
+// the menus can change the settings, channel name, camera speed and volume, everything else is kept as it was in the file
+fn save_config(
+    mut save_events: EventReader<SaveConfig>,
+    path: Res<ConfigPath>,
+    mut file: ResMut<FileConfig>,
+    mut config: ResMut<Config>,
+    channel_name: Res<ChannelName>,
+    settings: Res<Settings>,
+    free_fly: Res<FreeFly>,
+    global_volume: Res<GlobalVolume>,
+) {
+    if save_events.read().count() == 0 {
+        return;
+    }
+
+    let file = &mut file.0;
+    let config = &mut *config;
+    keep_change(
+        &mut file.channel_name,
+        &mut config.channel_name,
+        channel_name.0.clone(),
+    );
+    keep_change(
+        &mut file.filter_joins,
+        &mut config.filter_joins,
+        settings.filter_joins,
+    );
+    keep_change(
+        &mut file.goats_per_player,
+        &mut config.goats_per_player,
+        settings.goats_per_player,
+    );
+    keep_change(
+        &mut file.control_mode,
+        &mut config.control_mode,
+        settings.control_mode,
+    );
+    keep_change(
+        &mut file.democracy_interval,
+        &mut config.democracy_interval,
+        settings.democracy_interval,
+    );
+    keep_change(
+        &mut file.team_mode,
+        &mut config.team_mode,
+        settings.team_mode,
+    );
+    keep_change(
+        &mut file.game_mode,
+        &mut config.game_mode,
+        settings.game_mode,
+    );
+    keep_change(&mut file.hazards, &mut config.hazards, settings.hazards);
+    keep_change(
+        &mut file.max_goats,
+        &mut config.max_goats,
+        settings.max_goats,
+    );
+    keep_change(
+        &mut file.auto_start_goats,
+        &mut config.auto_start_goats,
+        settings.auto_start_goats,
+    );
+    keep_change(
+        &mut file.auto_start_secs,
+        &mut config.auto_start_secs,
+        settings.auto_start_secs,
+    );
+    keep_change(
+        &mut file.join_selection,
+        &mut config.join_selection,
+        settings.join_selection,
+    );
+    keep_change(
+        &mut file.sub_priority,
+        &mut config.sub_priority,
+        settings.sub_priority,
+    );
+    keep_change(
+        &mut file.late_joins,
+        &mut config.late_joins,
+        settings.late_joins,
+    );
+    keep_change(&mut file.respawn, &mut config.respawn, settings.respawn);
+    keep_change(
+        &mut file.respawn_secs,
+        &mut config.respawn_secs,
+        settings.respawn_secs,
+    );
+    keep_change(
+        &mut file.respawn_round_secs,
+        &mut config.respawn_round_secs,
+        settings.respawn_round_secs,
+    );
+    keep_change(
+        &mut file.spawn_layout,
+        &mut config.spawn_layout,
+        settings.spawn_layout,
+    );
+    keep_change(
+        &mut file.spawn_facing,
+        &mut config.spawn_facing,
+        settings.spawn_facing,
+    );
+    keep_change(
+        &mut file.spawn_margin,
+        &mut config.spawn_margin,
+        settings.spawn_margin,
+    );
+    keep_change(
+        &mut file.spawn_spacing,
+        &mut config.spawn_spacing,
+        settings.spawn_spacing,
+    );
+    keep_change(
+        &mut file.goat_stats,
+        &mut config.goat_stats,
+        settings.goat_stats,
+    );
+    keep_change(
+        &mut file.camera_speed,
+        &mut config.camera_speed,
+        free_fly.speed,
+    );
+    keep_change(
+        &mut file.volume,
+        &mut config.volume,
+        global_volume.volume.to_linear(),
+    );
+
+    match file.save(&path.0) {
+        Ok(()) => info!("saved settings to {}", path.0.display()),
+        Err(err) => error!("couldnt save settings: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn flag(key: &str, value: &str) -> (String, String) {
+        (key.to_owned(), value.to_owned())
+    }
+
+    #[test]
+    fn flags_parse() {
+        assert_eq!(
+            parse_flags(&args(&[
+                "--gravity=30",
+                "--channel-name",
+                "someone",
+                "--hazards",
+                "--max_goats",
+                "none",
+                "stray",
+                "--late-joins",
+            ])),
+            vec![
+                flag("gravity", "30"),
+                flag("channel-name", "someone"),
+                flag("hazards", "true"),
+                flag("max_goats", "none"),
+                flag("late-joins", "true"),
+            ]
+        );
+        assert!(parse_flags(&[]).is_empty());
+    }
+
+    #[test]
+    fn settings_are_set_by_name() {
+        let mut config = Config::default();
+        config.set("channel-name", "\"SomeOne\"").unwrap();
+        assert_eq!(config.channel_name, "someone");
+        config.set("gravity", "30").unwrap();
+        assert_eq!(config.gravity, 30.);
+        config.set("hazards", "false").unwrap();
+        assert!(!config.hazards);
+        config.set("game_mode", "Soccer").unwrap();
+        assert_eq!(config.game_mode, GameMode::Soccer);
+
+        config.set("max_goats", "20").unwrap();
+        assert_eq!(config.max_goats, Some(20));
+        config.set("max_goats", "Some(30)").unwrap();
+        assert_eq!(config.max_goats, Some(30));
+        config.set("max_goats", "None").unwrap();
+        assert_eq!(config.max_goats, None);
+    }
+
+    #[test]
+    fn settings_are_kept_in_range() {
+        let mut config = Config::default();
+        config.set("goats_per_player", "0").unwrap();
+        assert_eq!(config.goats_per_player, 1);
+        config.set("volume", "3").unwrap();
+        assert_eq!(config.volume, 1.);
+        config.set("spawn_margin", "1000").unwrap();
+        assert_eq!(config.spawn_margin, 250.);
+        config.set("spawn_spacing", "0").unwrap();
+        assert_eq!(config.spawn_spacing, 5.);
+    }
+
+    #[test]
+    fn bad_settings_are_errors() {
+        let mut config = Config::default();
+        assert!(config.set("gravityy", "30").is_err());
+        assert!(config.set("gravity", "lots").is_err());
+        assert!(config.set("goats_per_player", "-1").is_err());
+        assert_eq!(config.gravity, Config::default().gravity);
+    }
+
+    #[test]
+    fn only_menu_changes_are_saved() {
+        let mut file = 1;
+        let mut loaded = 2;
+        // the menu still shows the override, so the file keeps its own value
+        keep_change(&mut file, &mut loaded, 2);
+        assert_eq!((file, loaded), (1, 2));
+        keep_change(&mut file, &mut loaded, 3);
+        assert_eq!((file, loaded), (3, 3));
+    }
+}
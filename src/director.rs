@@ -0,0 +1,235 @@
+use crate::{hazard, player, spectator_camera::CameraMode, util, GameState};
+use bevy::prelude::*;
+
+// shortest time a shot is held before the director looks for something better
+const MIN_SHOT_SECS: f32 = 4.;
+// how long an explosion keeps being interesting
+const EXPLOSION_MEMORY_SECS: f32 = 3.;
+// cut instead of gliding when the next shot is this far away
+const CUT_DISTANCE: f32 = 150.;
+
+pub struct DirectorPlugin;
+
+impl Plugin for DirectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Director::default()).add_systems(
+            Update,
+            (remember_explosions, pick_shot, frame_shot)
+                .chain()
+                .run_if(in_state(GameState::Spectating).and(resource_equals(CameraMode::Auto))),
+        );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Shot {
+    // high up looking down at a point
+    Wide(Vec3),
+    // behind a goat
+    Chase(Entity),
+    // right next to a goat
+    CloseUp(Entity),
+    // both of the last two goats
+    Duel(Entity, Entity),
+}
+
+#[derive(Resource)]
+struct Director {
+    shot: Shot,
+    shot_age: f32,
+    // snap to the next frame instead of gliding
+    cut: bool,
+    explosions: Vec<(Vec3, f32)>,
+}
+
+impl Default for Director {
+    fn default() -> Self {
+        Director {
+            shot: Shot::Wide(Vec3::ZERO),
+            shot_age: MIN_SHOT_SECS,
+            cut: true,
+            explosions: vec![],
+        }
+    }
+}
+
+fn remember_explosions(
+    time: Res<Time>,
+    mut director: ResMut<Director>,
+    explosion_query: Query<&Transform, Added<util::Explosion>>,
+) {
+    let now = time.elapsed_secs();
+    director
+        .explosions
+        .retain(|(_, at)| now - at < EXPLOSION_MEMORY_SECS);
+    for trans in &explosion_query {
+        director.explosions.push((trans.translation, now));
+    }
+}
+
+// how close a goat is to dying, 0 is fine and 1 is basically dead
+fn danger(trans: &Transform, burn: Option<&hazard::Burn>) -> f32 {
+    let tilt = (1. - trans.up().dot(Vec3::Y)).clamp(0., 1.);
+    let edge = ((trans.translation.xz().abs().max_element() - 250.) / 50.).clamp(0., 1.);
+    let cooking = burn.map_or(0., |burn| burn.0 / hazard::LAVA_LETHAL_SECS);
+    tilt.max(edge).max(cooking)
+}
+
+fn pick_shot(
+    time: Res<Time>,
+    mut director: ResMut<Director>,
+    camera_query: Single<&Transform, With<Camera>>,
+    player_query: Query<(Entity, &Transform, Option<&hazard::Burn>), With<player::Player>>,
+) {
+    director.shot_age += time.delta_secs();
+
+    let shot_alive = match director.shot {
+        Shot::Wide(_) => true,
+        Shot::Chase(goat) | Shot::CloseUp(goat) => player_query.contains(goat),
+        Shot::Duel(a, b) => player_query.contains(a) && player_query.contains(b),
+    };
+    if shot_alive && director.shot_age < MIN_SHOT_SECS {
+        return;
+    }
+
+    let goats = player_query.iter().collect::<Vec<_>>();
+    let next_shot = if goats.len() == 2 {
+        Shot::Duel(goats[0].0, goats[1].0)
+    } else {
+        // score every goat by how much is going on around it
+        let mut best: Option<(Entity, f32, bool)> = None;
+        for (entity, trans, burn) in &goats {
+            let crowd = goats
+                .iter()
+                .filter(|(_, other, _)| {
+                    other.translation.distance_squared(trans.translation) < 30. * 30.
+                })
+                .count() as f32
+                - 1.;
+            let booms = director
+                .explosions
+                .iter()
+                .filter(|(pos, _)| pos.distance_squared(trans.translation) < 40. * 40.)
+                .count() as f32;
+            let danger = danger(trans, *burn);
+            let score = crowd + booms * 3. + danger * 5.;
+
+            if best.is_none_or(|(_, best_score, _)| score > best_score) {
+                best = Some((*entity, score, danger > 0.5 || booms > 0.));
+            }
+        }
+
+        match best {
+            Some((goat, _, true)) => Shot::CloseUp(goat),
+            Some((goat, score, false)) if score >= 2. => Shot::Chase(goat),
+            _ => {
+                let center = goats
+                    .iter()
+                    .map(|(_, trans, _)| trans.translation)
+                    .sum::<Vec3>()
+                    / goats.len().max(1) as f32;
+                Shot::Wide(center)
+            }
+        }
+    };
+
+    if next_shot == director.shot && shot_alive {
+        return;
+    }
+
+    let cut = shot_target(next_shot, &player_query)
+        .is_some_and(|target| target.translation.distance(camera_query.translation) > CUT_DISTANCE);
+    director.shot = next_shot;
+    director.shot_age = 0.;
+    director.cut = cut;
+}
+
+// where the camera should be for a shot right now
+fn shot_target(
+    shot: Shot,
+    player_query: &Query<(Entity, &Transform, Option<&hazard::Burn>), With<player::Player>>,
+) -> Option<Transform> {
+    let goat_pos = |goat: Entity| player_query.get(goat).ok().map(|(_, trans, _)| *trans);
+
+    match shot {
+        Shot::Wide(focus) => Some(
+            Transform::from_translation(focus + vec3(0., 150., -150.)).looking_at(focus, Vec3::Y),
+        ),
+        Shot::Chase(goat) => goat_pos(goat).map(|trans| {
+            let forward = trans.forward().with_y(0.).normalize_or_zero();
+            Transform::from_translation(trans.translation - forward * 25. + Vec3::Y * 10.)
+                .looking_at(trans.translation + forward * 10., Vec3::Y)
+        }),
+        Shot::CloseUp(goat) => goat_pos(goat).map(|trans| {
+            let side = trans.right().with_y(0.).normalize_or_zero();
+            Transform::from_translation(trans.translation + side * 10. + Vec3::Y * 4.)
+                .looking_at(trans.translation, Vec3::Y)
+        }),
+        Shot::Duel(a, b) => {
+            let (a, b) = (goat_pos(a)?.translation, goat_pos(b)?.translation);
+            let middle = (a + b) / 2.;
+            let across = (b - a).with_y(0.);
+            let side = across.cross(Vec3::Y).normalize_or(Vec3::Z);
+            Some(
+                Transform::from_translation(
+                    middle + side * (across.length() * 0.8 + 20.) + Vec3::Y * 15.,
+                )
+                .looking_at(middle, Vec3::Y),
+            )
+        }
+    }
+}
+
+fn frame_shot(
+    time: Res<Time>,
+    mut director: ResMut<Director>,
+    mut camera_query: Single<&mut Transform, (With<Camera>, Without<player::Player>)>,
+    player_query: Query<(Entity, &Transform, Option<&hazard::Burn>), With<player::Player>>,
+) {
+    let Some(target) = shot_target(director.shot, &player_query) else {
+        return;
+    };
+
+    if director.cut {
+        **camera_query = target;
+        director.cut = false;
+        return;
+    }
+
+    let t = 1. - (-2. * time.delta_secs()).exp();
+    camera_query.translation = camera_query.translation.lerp(target.translation, t);
+    camera_query.rotation = camera_query.rotation.slerp(target.rotation, t);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upright_goats_in_the_middle_are_safe() {
+        let trans = Transform::from_xyz(10., 0., -20.);
+        assert_eq!(danger(&trans, None), 0.);
+    }
+
+    #[test]
+    fn goats_near_the_edge_are_in_danger() {
+        assert_eq!(danger(&Transform::from_xyz(275., 0., 0.), None), 0.5);
+        assert_eq!(danger(&Transform::from_xyz(0., 0., -320.), None), 1.);
+    }
+
+    #[test]
+    fn tipped_over_goats_are_in_danger() {
+        let on_its_back = Transform::from_rotation(Quat::from_rotation_x(std::f32::consts::PI));
+        assert_eq!(danger(&on_its_back, None), 1.);
+        let on_its_side =
+            Transform::from_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2));
+        assert!(danger(&on_its_side, None) > 0.99);
+    }
+
+    #[test]
+    fn burning_goats_are_in_danger() {
+        let trans = Transform::default();
+        let half_cooked = hazard::Burn(hazard::LAVA_LETHAL_SECS / 2.);
+        assert_eq!(danger(&trans, Some(&half_cooked)), 0.5);
+    }
+}
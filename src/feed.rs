@@ -0,0 +1,274 @@
+use crate::moderation::NameFilter;
+use crate::player::{self, DeathCause};
+use bevy::prelude::*;
+
+pub struct FeedPlugin;
+
+impl Plugin for FeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FeedStyle::default())
+            .add_event::<FeedEvent>()
+            .add_systems(Startup, setup_feed)
+            .add_systems(
+                Update,
+                (
+                    collect_feed_events,
+                    show_feed_events,
+                    fade_feed_entries,
+                    hide_feed.run_if(resource_changed::<FeedStyle>),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// something worth telling everyone about, read this for announcers and overlays
+#[derive(Event, Clone, Debug)]
+pub enum FeedEvent {
+    Joined {
+        name: String,
+    },
+    Died {
+        name: String,
+        killer: Option<String>,
+        cause: DeathCause,
+    },
+    // someone asked for their all time stats
+    Stats {
+        name: String,
+        summary: String,
+    },
+    // a mod kicked or banned someone or changed the round
+    Moderation {
+        text: String,
+    },
+    // someone spent channel points or bits on the round
+    Redeemed {
+        name: String,
+        action: String,
+    },
+}
+
+impl FeedEvent {
+    /// names are logins until here, blocked ones get swapped for what the filter shows instead
+    pub fn text(&self, name_filter: &NameFilter) -> String {
+        match self {
+            FeedEvent::Joined { name } => format!("{} joined", name_filter.display(name)),
+            FeedEvent::Stats { name, summary } => {
+                format!("{}: {summary}", name_filter.display(name))
+            }
+            // already filtered when it was written
+            FeedEvent::Moderation { text } => text.clone(),
+            FeedEvent::Redeemed { name, action } => {
+                format!("{} redeemed {action}", name_filter.display(name))
+            }
+            FeedEvent::Died {
+                name,
+                killer: Some(killer),
+                cause,
+            } => {
+                let name = name_filter.display(name);
+                let killer = name_filter.display(killer);
+                match cause {
+                    DeathCause::Rammed => format!("{killer} rammed {name}"),
+                    DeathCause::BlownUp => format!("{killer} blew up {name}"),
+                    DeathCause::FellOff => format!("{killer} knocked {name} off the map"),
+                    DeathCause::Lava => format!("{killer} pushed {name} into lava"),
+                    DeathCause::Flipped => format!("{killer} flipped {name}"),
+                }
+            }
+            FeedEvent::Died {
+                name,
+                killer: None,
+                cause,
+            } => {
+                let name = name_filter.display(name);
+                match cause {
+                    DeathCause::Rammed => format!("{name} got rammed"),
+                    DeathCause::BlownUp => format!("{name} was blown up by the streamer"),
+                    DeathCause::FellOff => format!("{name} fell off the map"),
+                    DeathCause::Lava => format!("{name} melted in lava"),
+                    DeathCause::Flipped => format!("{name} fell over"),
+                }
+            }
+        }
+    }
+}
+
+/// how the feed in the top right looks
+#[derive(Resource, Clone, Debug)]
+pub struct FeedStyle {
+    pub font_size: f32,
+    pub max_entries: usize,
+    // seconds an entry stays fully visible, then how long it takes to fade out
+    pub show_secs: f32,
+    pub fade_secs: f32,
+    pub join_color: Color,
+    pub death_color: Color,
+    pub info_color: Color,
+    pub show_joins: bool,
+    pub visible: bool,
+}
+
+impl Default for FeedStyle {
+    fn default() -> Self {
+        FeedStyle {
+            font_size: 22.,
+            max_entries: 8,
+            show_secs: 5.,
+            fade_secs: 1.5,
+            join_color: Color::srgb(0.6, 1., 0.6),
+            death_color: Color::WHITE,
+            info_color: Color::srgb(0.6, 0.8, 1.),
+            show_joins: true,
+            visible: true,
+        }
+    }
+}
+
+#[derive(Component)]
+struct FeedRoot;
+
+#[derive(Component)]
+struct FeedEntry {
+    age: f32,
+    color: Color,
+}
+
+fn setup_feed(mut commands: Commands) {
+    commands.spawn((
+        FeedRoot,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.),
+            right: Val::Px(10.),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::FlexEnd,
+            row_gap: Val::Px(4.),
+            ..Default::default()
+        },
+    ));
+}
+
+fn collect_feed_events(
+    mut join_events: EventReader<player::GoatJoined>,
+    mut death_events: EventReader<player::GoatDied>,
+    mut feed_events: EventWriter<FeedEvent>,
+) {
+    for event in join_events.read() {
+        feed_events.write(FeedEvent::Joined {
+            name: event.name.clone(),
+        });
+    }
+    for event in death_events.read() {
+        feed_events.write(FeedEvent::Died {
+            name: event.name.clone(),
+            killer: event.killer.clone(),
+            cause: event.cause,
+        });
+    }
+}
+
+fn show_feed_events(
+    mut commands: Commands,
+    style: Res<FeedStyle>,
+    name_filter: Res<NameFilter>,
+    mut feed_events: EventReader<FeedEvent>,
+    root: Single<Entity, With<FeedRoot>>,
+) {
+    for event in feed_events.read() {
+        let color = match event {
+            FeedEvent::Joined { .. } if !style.show_joins => continue,
+            FeedEvent::Joined { .. } => style.join_color,
+            FeedEvent::Died { .. } => style.death_color,
+            FeedEvent::Stats { .. } | FeedEvent::Moderation { .. } | FeedEvent::Redeemed { .. } => {
+                style.info_color
+            }
+        };
+        commands.spawn((
+            ChildOf(*root),
+            FeedEntry { age: 0., color },
+            Text::new(event.text(&name_filter)),
+            TextFont::default().with_font_size(style.font_size),
+            TextColor(color),
+        ));
+    }
+}
+
+fn hide_feed(style: Res<FeedStyle>, mut root: Single<&mut Visibility, With<FeedRoot>>) {
+    **root = if style.visible {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+}
+
+fn fade_feed_entries(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    style: Res<FeedStyle>,
+    mut entry_query: Query<(Entity, &mut FeedEntry, &mut TextColor)>,
+    root_query: Single<Option<&Children>, With<FeedRoot>>,
+) {
+    // the oldest entries go first when the feed is full
+    let overflow = match *root_query {
+        Some(children) => &children[..children.len().saturating_sub(style.max_entries)],
+        None => &[],
+    };
+
+    for (entity, mut entry, mut color) in &mut entry_query {
+        entry.age += time.delta_secs();
+        let fade = ((entry.age - style.show_secs) / style.fade_secs).clamp(0., 1.);
+        if fade >= 1. || overflow.contains(&entity) {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        color.0 = entry.color.with_alpha(1. - fade);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn died(name: &str, killer: Option<&str>, cause: DeathCause) -> FeedEvent {
+        FeedEvent::Died {
+            name: name.to_owned(),
+            killer: killer.map(|killer| killer.to_owned()),
+            cause,
+        }
+    }
+
+    #[test]
+    fn knockouts_name_the_killer() {
+        let name_filter = NameFilter::new(&[]);
+        assert_eq!(
+            died("goat", Some("ram"), DeathCause::Rammed).text(&name_filter),
+            "ram rammed goat"
+        );
+        assert_eq!(
+            died("goat", Some("ram"), DeathCause::FellOff).text(&name_filter),
+            "ram knocked goat off the map"
+        );
+        assert_eq!(
+            died("goat", None, DeathCause::FellOff).text(&name_filter),
+            "goat fell off the map"
+        );
+        assert_eq!(
+            died("goat", None, DeathCause::BlownUp).text(&name_filter),
+            "goat was blown up by the streamer"
+        );
+    }
+
+    #[test]
+    fn blocked_names_never_reach_the_feed() {
+        let name_filter = NameFilter::new(&["bad".to_owned()]);
+        let alias = name_filter.display("badgoat");
+        let joined = FeedEvent::Joined {
+            name: "badgoat".to_owned(),
+        };
+        assert_eq!(joined.text(&name_filter), format!("{alias} joined"));
+        let text = died("nicegoat", Some("badgoat"), DeathCause::Lava).text(&name_filter);
+        assert_eq!(text, format!("{alias} pushed nicegoat into lava"));
+    }
+}
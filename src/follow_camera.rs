@@ -0,0 +1,362 @@
+use crate::{
+    goat_stats::GoatStats, moderation, pause, player, spectator_camera::CameraMode, GameState,
+};
+use avian3d::prelude::*;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+
+// how long the camera stays on the killer or the explosion after the followed goat dies
+const HOLD_SECS: f32 = 3.;
+
+pub struct FollowCameraPlugin;
+
+impl Plugin for FollowCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FollowCam::default())
+            .insert_resource(FollowSearch::default())
+            .add_systems(OnEnter(GameState::Spectating), setup_follow_text)
+            .add_systems(
+                Update,
+                (
+                    (
+                        type_name,
+                        (select_by_click, cycle_goats, toggle_framing).run_if(not_searching),
+                    )
+                        .chain()
+                        .run_if(pause::not_paused),
+                    follow_goat,
+                    update_follow_text,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Spectating)),
+            )
+            .add_systems(OnExit(GameState::Spectating), despawn_follow_text);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+enum Framing {
+    #[default]
+    ThirdPerson,
+    Orbit,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum HoldOn {
+    Goat(Entity),
+    Spot(Vec3),
+}
+
+#[derive(Resource, Default)]
+struct FollowCam {
+    goat: Option<(Entity, String)>,
+    framing: Framing,
+    orbit_angle: f32,
+    // what to look at after the goat died and for how much longer
+    hold: Option<(HoldOn, f32)>,
+}
+
+impl FollowCam {
+    fn follow(&mut self, goat: Entity, name: &str) {
+        self.goat = Some((goat, name.to_owned()));
+        self.hold = None;
+    }
+}
+
+/// the name typed after pressing / , none when not typing
+#[derive(Resource, Default)]
+pub struct FollowSearch(Option<String>);
+
+pub fn not_searching(search: Res<FollowSearch>) -> bool {
+    search.0.is_none()
+}
+
+#[derive(Component)]
+struct FollowText;
+
+pub fn type_name(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    input: Res<ButtonInput<KeyCode>>,
+    mut search: ResMut<FollowSearch>,
+    mut follow_cam: ResMut<FollowCam>,
+    mut camera_mode: ResMut<CameraMode>,
+    name_filter: Res<moderation::NameFilter>,
+    player_query: Query<(Entity, &player::Player)>,
+) {
+    let Some(text) = &mut search.0 else {
+        keyboard_events.clear();
+        if input.just_pressed(KeyCode::Slash) {
+            search.0 = Some(String::new());
+            // stops wasd from flying the camera around while typing
+            *camera_mode = CameraMode::Follow;
+        }
+        return;
+    };
+
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Character(c) => text.push_str(c),
+            Key::Space => text.push(' '),
+            Key::Backspace => {
+                text.pop();
+            }
+            Key::Escape => {
+                search.0 = None;
+                if follow_cam.goat.is_none() {
+                    *camera_mode = CameraMode::Manual;
+                }
+                return;
+            }
+            Key::Enter => {
+                let found = player_query
+                    .iter()
+                    .filter(|(_, player)| matches_search(player.name(), text, &name_filter))
+                    .min_by_key(|(_, player)| player.name().len());
+                if let Some((goat, player)) = found {
+                    follow_cam.follow(goat, player.name());
+                }
+                search.0 = None;
+                return;
+            }
+            _ => {}
+        }
+    }
+}
+
+// the name on screen works too when its not the real one
+fn matches_search(name: &str, wanted: &str, name_filter: &moderation::NameFilter) -> bool {
+    let wanted = wanted.to_lowercase();
+    name.to_lowercase().contains(&wanted) || name_filter.display(name).contains(&wanted)
+}
+
+fn select_by_click(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    spatial_query: SpatialQuery,
+    window: Single<&Window>,
+    camera_query: Single<(&Camera, &GlobalTransform)>,
+    player_query: Query<&player::Player>,
+    mut follow_cam: ResMut<FollowCam>,
+    mut camera_mode: ResMut<CameraMode>,
+) {
+    if !mouse_input.just_pressed(MouseButton::Middle) {
+        return;
+    }
+
+    // the cursor is locked while spectating so this is usually the middle of the screen
+    let cursor = window.cursor_position().unwrap_or(window.size() / 2.);
+    let (camera, camera_trans) = *camera_query;
+    let Ok(ray) = camera.viewport_to_world(camera_trans, cursor) else {
+        return;
+    };
+    let Some(hit) = spatial_query.cast_ray(
+        ray.origin,
+        ray.direction,
+        1000.,
+        true,
+        &SpatialQueryFilter::default(),
+    ) else {
+        return;
+    };
+
+    if let Ok(player) = player_query.get(hit.entity) {
+        follow_cam.follow(hit.entity, player.name());
+        *camera_mode = CameraMode::Follow;
+    }
+}
+
+fn cycle_goats(
+    input: Res<ButtonInput<KeyCode>>,
+    player_query: Query<(Entity, &player::Player)>,
+    mut follow_cam: ResMut<FollowCam>,
+    mut camera_mode: ResMut<CameraMode>,
+) {
+    if !input.just_pressed(KeyCode::Tab) {
+        return;
+    }
+
+    let mut goats = player_query.iter().collect::<Vec<_>>();
+    if goats.is_empty() {
+        return;
+    }
+    goats.sort_by(|a, b| a.1.name().cmp(b.1.name()));
+
+    let current = follow_cam
+        .goat
+        .as_ref()
+        .and_then(|(goat, _)| goats.iter().position(|(entity, _)| entity == goat));
+    let next = match current {
+        Some(i) => (i + 1) % goats.len(),
+        None => 0,
+    };
+    let (goat, player) = goats[next];
+    follow_cam.follow(goat, player.name());
+    *camera_mode = CameraMode::Follow;
+}
+
+fn toggle_framing(input: Res<ButtonInput<KeyCode>>, mut follow_cam: ResMut<FollowCam>) {
+    if input.just_pressed(KeyCode::KeyV) {
+        follow_cam.framing = match follow_cam.framing {
+            Framing::ThirdPerson => Framing::Orbit,
+            Framing::Orbit => Framing::ThirdPerson,
+        };
+    }
+}
+
+fn follow_goat(
+    time: Res<Time>,
+    mut death_events: EventReader<player::GoatDied>,
+    mouse_motion: Res<bevy::input::mouse::AccumulatedMouseMotion>,
+    mut follow_cam: ResMut<FollowCam>,
+    mut camera_mode: ResMut<CameraMode>,
+    mut camera_query: Single<&mut Transform, (With<Camera>, Without<player::Player>)>,
+    player_query: Query<(Entity, &Transform, &player::Player)>,
+) {
+    let follow_cam = &mut *follow_cam;
+    for event in death_events.read() {
+        let Some((_, name)) = &follow_cam.goat else {
+            continue;
+        };
+        if *name != event.name {
+            continue;
+        }
+
+        let killer = event.killer.as_ref().and_then(|killer| {
+            player_query
+                .iter()
+                .find(|(_, _, player)| player.name() == killer)
+        });
+        let hold_on = match killer {
+            Some((killer, _, _)) => HoldOn::Goat(killer),
+            None => HoldOn::Spot(event.pos),
+        };
+        follow_cam.goat = None;
+        follow_cam.hold = Some((hold_on, HOLD_SECS));
+    }
+
+    if *camera_mode != CameraMode::Follow {
+        return;
+    }
+
+    let dt = time.delta_secs();
+    let t = 1. - (-5. * dt).exp();
+
+    let goat = match (&follow_cam.goat, &mut follow_cam.hold) {
+        (Some((goat, _)), _) => Some(*goat),
+        (None, Some((hold_on, time_left))) => {
+            *time_left -= dt;
+            if *time_left <= 0. {
+                follow_cam.hold = None;
+                *camera_mode = CameraMode::Manual;
+                return;
+            }
+            match *hold_on {
+                HoldOn::Goat(goat) => Some(goat),
+                HoldOn::Spot(spot) => {
+                    let target = camera_query.looking_at(spot, Vec3::Y).rotation;
+                    camera_query.rotation = camera_query.rotation.slerp(target, t);
+                    None
+                }
+            }
+        }
+        (None, None) => None,
+    };
+    let Some(goat_trans) = goat
+        .and_then(|goat| player_query.get(goat).ok())
+        .map(|(_, trans, _)| trans)
+    else {
+        return;
+    };
+
+    let goat_pos = goat_trans.translation;
+    let target = match follow_cam.framing {
+        Framing::ThirdPerson => {
+            let forward = goat_trans.forward().with_y(0.).normalize_or_zero();
+            Transform::from_translation(goat_pos - forward * 20. + Vec3::Y * 8.)
+                .looking_at(goat_pos + forward * 5., Vec3::Y)
+        }
+        Framing::Orbit => {
+            follow_cam.orbit_angle += 0.3 * dt - 8e-3 * mouse_motion.delta.x;
+            let angle = follow_cam.orbit_angle;
+            Transform::from_translation(goat_pos + vec3(angle.cos() * 25., 10., angle.sin() * 25.))
+                .looking_at(goat_pos, Vec3::Y)
+        }
+    };
+
+    camera_query.translation = camera_query.translation.lerp(target.translation, t);
+    camera_query.rotation = camera_query.rotation.slerp(target.rotation, t);
+}
+
+fn setup_follow_text(mut commands: Commands) {
+    commands.spawn((
+        FollowText,
+        Text::new(""),
+        TextFont::default().with_font_size(25.),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.),
+            left: Val::Px(10.),
+            ..Default::default()
+        },
+    ));
+}
+
+fn despawn_follow_text(mut commands: Commands, text_query: Query<Entity, With<FollowText>>) {
+    for text in &text_query {
+        commands.entity(text).despawn();
+    }
+}
+
+fn update_follow_text(
+    search: Res<FollowSearch>,
+    follow_cam: Res<FollowCam>,
+    camera_mode: Res<CameraMode>,
+    name_filter: Res<moderation::NameFilter>,
+    mut text_query: Single<&mut Text, With<FollowText>>,
+    stats_query: Query<&GoatStats>,
+) {
+    text_query.0 = match (&search.0, &follow_cam.goat) {
+        (Some(text), _) => "follow: ".to_owned() + text + "_",
+        (None, Some((goat, name))) if *camera_mode == CameraMode::Follow => {
+            let stats = stats_query
+                .get(*goat)
+                .map(|stats| "\n".to_owned() + &stats.describe())
+                .unwrap_or_default();
+            "following ".to_owned()
+                + &name_filter.display(name)
+                + " (tab: next, v: framing, c: free camera)"
+                + &stats
+        }
+        _ => String::new(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_found_by_any_part() {
+        let name_filter = moderation::NameFilter::new(&[]);
+        assert!(matches_search("SomeGoat 2", "goat", &name_filter));
+        assert!(matches_search("SomeGoat 2", "SOMEG", &name_filter));
+        assert!(matches_search("SomeGoat 2", "", &name_filter));
+        assert!(!matches_search("SomeGoat 2", "sheep", &name_filter));
+    }
+
+    #[test]
+    fn blocked_names_are_found_by_their_alias() {
+        let name_filter = moderation::NameFilter::new(&["bad".to_owned()]);
+        let alias = name_filter.display("badgoat");
+        assert!(matches_search("badgoat", &alias, &name_filter));
+        assert!(matches_search(
+            "badgoat",
+            &alias.to_uppercase(),
+            &name_filter
+        ));
+        assert!(!matches_search("nicegoat", &alias, &name_filter));
+    }
+}
@@ -0,0 +1,614 @@
+use crate::{menu, moderation::NameFilter, player, respawn::RespawnMode, team, GameState};
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+const HILL_RADIUS: f32 = 40.;
+const HILL_WIN_SECS: f32 = 30.;
+const KNOCKOUT_ROUND_SECS: f32 = 120.;
+const SOCCER_WIN_GOALS: f32 = 3.;
+const GOAL_HALF_WIDTH: f32 = 40.;
+const BALL_RADIUS: f32 = 6.;
+// goats drive at a spot this far behind the ball so they push it towards the goal they shoot at
+const BALL_AIM_DISTANCE: f32 = 2. * BALL_RADIUS;
+
+pub struct GameModePlugin;
+
+impl Plugin for GameModePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Scores::default())
+            .insert_resource(RoundTime::default())
+            .insert_resource(ForceEnd::default())
+            .insert_resource(RoundResult::default())
+            .add_systems(
+                OnEnter(GameState::Spectating),
+                (reset_round, setup_mode_hud, spawn_mode_props),
+            )
+            .add_systems(
+                Update,
+                update_mode_hud.run_if(in_state(GameState::Spectating)),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    tick_round_time,
+                    score_hill.run_if(mode_is(GameMode::KingOfTheHill)),
+                    score_knockouts.run_if(counts_knockouts),
+                    (score_goals, aim_at_ball).run_if(mode_is(GameMode::Soccer)),
+                    end,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Spectating)),
+            )
+            .add_systems(OnExit(GameState::Spectating), despawn_mode_hud);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum GameMode {
+    #[default]
+    LastStanding,
+    KingOfTheHill,
+    Knockouts,
+    Soccer,
+}
+
+impl GameMode {
+    pub fn next(self) -> Self {
+        match self {
+            GameMode::LastStanding => GameMode::KingOfTheHill,
+            GameMode::KingOfTheHill => GameMode::Knockouts,
+            GameMode::Knockouts => GameMode::Soccer,
+            GameMode::Soccer => GameMode::LastStanding,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GameMode::LastStanding => "last goat standing",
+            GameMode::KingOfTheHill => "king of the hill",
+            GameMode::Knockouts => "most knockouts",
+            GameMode::Soccer => "goat soccer",
+        }
+    }
+
+    // team_round is whether the round started with more than one team
+    fn is_over(
+        self,
+        alive: &[(&player::Player, Option<&team::Team>)],
+        scores: &Scores,
+        time: f32,
+        team_round: bool,
+    ) -> bool {
+        // every mode stops once theres nobody left to fight, the last goats can go out together
+        if alive.len() <= 1 {
+            return true;
+        }
+
+        match self {
+            GameMode::LastStanding => {
+                // teamless goats are a team of one so this is the same as one goat left.
+                // when everyone started on the same team its every goat for itself
+                let first = alive.first().and_then(|(_, team)| *team);
+                team_round && first.is_some() && alive.iter().all(|(_, team)| *team == first)
+            }
+            GameMode::Knockouts => time >= KNOCKOUT_ROUND_SECS,
+            _ => self.is_won(scores),
+        }
+    }
+
+    // the modes that end on a score, with respawns thats the only way they end by themselves
+    fn is_won(self, scores: &Scores) -> bool {
+        match self {
+            GameMode::KingOfTheHill => scores.best() >= HILL_WIN_SECS,
+            GameMode::Soccer => scores.best() >= SOCCER_WIN_GOALS,
+            GameMode::LastStanding | GameMode::Knockouts => false,
+        }
+    }
+
+    // winners are goat names, the title and lines are what the end screen shows
+    fn result(
+        self,
+        alive: &[(&player::Player, Option<&team::Team>)],
+        scores: &Scores,
+        name_filter: &NameFilter,
+        team_round: bool,
+    ) -> RoundResult {
+        match self {
+            GameMode::LastStanding => {
+                // with teams the winners are everyone still standing on the last team
+                match alive
+                    .iter()
+                    .find_map(|(_, team)| *team)
+                    .filter(|_| team_round)
+                {
+                    Some(team) => {
+                        let names = alive
+                            .iter()
+                            .map(|(player, _)| player.name().to_owned())
+                            .collect::<Vec<_>>();
+                        RoundResult {
+                            title: team.name().to_owned() + " TEAM WON",
+                            lines: names.iter().map(|name| name_filter.display(name)).collect(),
+                            winners: names,
+                        }
+                    }
+                    None => {
+                        let winner = alive.last().map(|(player, _)| player.name().to_owned());
+                        RoundResult {
+                            title: winner
+                                .as_ref()
+                                .map_or("NOBODY".to_owned(), |winner| name_filter.display(winner))
+                                + " WON",
+                            lines: vec![],
+                            winners: winner.into_iter().collect(),
+                        }
+                    }
+                }
+            }
+            _ => {
+                // nobody wins a tie for first
+                let top = match scores.top(2)[..] {
+                    [(_, first), (_, second)] if first == second => None,
+                    [(key, _), ..] => Some(key.to_string()),
+                    [] => None,
+                };
+                let tied = top.is_none() && !scores.0.is_empty();
+                // a team key means everyone still on that team won
+                let winners = match &top {
+                    Some(key) if team::Team::ALL.iter().any(|team| team.name() == key) => alive
+                        .iter()
+                        .filter(|(_, team)| team.is_some_and(|team| team.name() == key))
+                        .map(|(player, _)| player.name().to_owned())
+                        .collect(),
+                    Some(key) => vec![key.clone()],
+                    None => vec![],
+                };
+                RoundResult {
+                    title: match top {
+                        Some(key) => name_filter.display(&key) + " WON",
+                        None if tied => "DRAW".to_owned(),
+                        None => "NOBODY WON".to_owned(),
+                    },
+                    lines: scores
+                        .top(5)
+                        .iter()
+                        .map(|(key, score)| {
+                            format!(
+                                "{}: {}",
+                                name_filter.display(key),
+                                self.format_score(*score)
+                            )
+                        })
+                        .collect(),
+                    winners,
+                }
+            }
+        }
+    }
+
+    fn hud_text(
+        self,
+        alive: &[(&player::Player, Option<&team::Team>)],
+        scores: &Scores,
+        time_left: Option<f32>,
+        name_filter: &NameFilter,
+    ) -> String {
+        let leaders = scores
+            .top(3)
+            .iter()
+            .map(|(key, score)| {
+                format!(
+                    "{}: {}",
+                    name_filter.display(key),
+                    self.format_score(*score)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let text = match self {
+            GameMode::LastStanding => {
+                let mut per_team: BTreeMap<&str, u32> = BTreeMap::new();
+                for team in alive.iter().filter_map(|(_, team)| *team) {
+                    *per_team.entry(team.name()).or_default() += 1;
+                }
+                let mut text = format!("{} goats left", alive.len());
+                for (team, count) in per_team {
+                    text += &format!("\n{team}: {count}");
+                }
+                text
+            }
+            GameMode::KingOfTheHill => {
+                format!("hold the hill for {HILL_WIN_SECS}s\n{leaders}")
+            }
+            GameMode::Knockouts => format!("most knockouts\n{leaders}"),
+            GameMode::Soccer => format!(
+                "RED {} - {} BLUE",
+                scores.get(team::Team::Red.name()),
+                scores.get(team::Team::Blue.name())
+            ),
+        };
+        match time_left {
+            Some(left) => {
+                let left = left.max(0.) as u32;
+                format!("{}:{:02} left\n{text}", left / 60, left % 60)
+            }
+            None => text,
+        }
+    }
+
+    fn format_score(self, score: f32) -> String {
+        match self {
+            GameMode::KingOfTheHill => format!("{score:.1}s"),
+            _ => format!("{score}"),
+        }
+    }
+}
+
+fn mode_is(mode: GameMode) -> impl Fn(Res<menu::Settings>) -> bool {
+    move |settings: Res<menu::Settings>| settings.game_mode == mode
+}
+
+/// the mode that decides the score, last goat standing makes no sense when goats come back
+/// so those rounds count knockouts instead
+pub fn scoring_mode(settings: &menu::Settings) -> GameMode {
+    match (settings.game_mode, settings.respawn) {
+        (GameMode::LastStanding, RespawnMode::Endless | RespawnMode::Timed) => GameMode::Knockouts,
+        (mode, _) => mode,
+    }
+}
+
+fn counts_knockouts(settings: Res<menu::Settings>) -> bool {
+    scoring_mode(&settings) == GameMode::Knockouts
+}
+
+// seconds until the clock ends the round, if theres a clock
+fn time_left(settings: &menu::Settings, mode: GameMode, time: f32) -> Option<f32> {
+    match settings.respawn {
+        RespawnMode::Off => (mode == GameMode::Knockouts).then(|| KNOCKOUT_ROUND_SECS - time),
+        RespawnMode::Endless => None,
+        RespawnMode::Timed => Some(settings.respawn_round_secs - time),
+    }
+}
+
+// goats score for themselves unless theyre on a team
+fn score_key(player: &player::Player, team: Option<&team::Team>) -> String {
+    match team {
+        Some(team) => team.name().to_owned(),
+        None => player.name().to_owned(),
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Scores(HashMap<String, f32>);
+
+impl Scores {
+    fn add(&mut self, key: String, amount: f32) {
+        *self.0.entry(key).or_default() += amount;
+    }
+
+    fn get(&self, key: &str) -> f32 {
+        self.0.get(key).copied().unwrap_or(0.)
+    }
+
+    fn best(&self) -> f32 {
+        self.0.values().copied().fold(0., f32::max)
+    }
+
+    // highest first, names break ties so the order doesnt change between runs
+    fn top(&self, n: usize) -> Vec<(&String, f32)> {
+        let mut scores = self.0.iter().map(|(k, v)| (k, *v)).collect::<Vec<_>>();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(b.0)));
+        scores.truncate(n);
+        scores
+    }
+}
+
+// seconds since the round started
+#[derive(Resource, Default)]
+pub struct RoundTime(pub f32);
+
+/// what the end screen shows, filled in by the mode when the round ends
+#[derive(Resource, Default)]
+pub struct RoundResult {
+    pub title: String,
+    pub lines: Vec<String>,
+    // names of the goats that won
+    pub winners: Vec<String>,
+}
+
+/// ends the round on the next tick no matter how its going
+#[derive(Resource, Default)]
+pub struct ForceEnd(pub bool);
+
+/// things that only belong to one round, cleared out when its restarted
+#[derive(Component)]
+pub struct RoundProp;
+
+#[derive(Component)]
+struct ModeHud;
+
+#[derive(Component)]
+struct SoccerBall;
+
+// where the goats of a team drive to get behind the ball
+#[derive(Component)]
+struct BallAim(team::Team);
+
+fn reset_round(
+    mut scores: ResMut<Scores>,
+    mut round_time: ResMut<RoundTime>,
+    mut force_end: ResMut<ForceEnd>,
+) {
+    scores.0.clear();
+    round_time.0 = 0.;
+    force_end.0 = false;
+}
+
+fn tick_round_time(time: Res<Time>, mut round_time: ResMut<RoundTime>) {
+    round_time.0 += time.delta_secs();
+}
+
+fn spawn_mode_props(
+    mut commands: Commands,
+    settings: Res<menu::Settings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    match settings.game_mode {
+        GameMode::LastStanding | GameMode::Knockouts => {}
+        GameMode::KingOfTheHill => {
+            commands.spawn((
+                RoundProp,
+                Mesh3d(meshes.add(Cylinder::new(HILL_RADIUS, 0.2))),
+                MeshMaterial3d(materials.add(StandardMaterial {
+                    base_color: Color::srgba(1., 0.8, 0., 0.4),
+                    alpha_mode: AlphaMode::Blend,
+                    unlit: true,
+                    ..Default::default()
+                })),
+                Transform::from_xyz(0., 0.6, 0.),
+            ));
+        }
+        GameMode::Soccer => {
+            commands.spawn((
+                RoundProp,
+                SoccerBall,
+                RigidBody::Dynamic,
+                Collider::sphere(BALL_RADIUS),
+                ComputedMass::new(20.),
+                Restitution::new(0.8),
+                Mesh3d(meshes.add(Sphere::new(BALL_RADIUS))),
+                MeshMaterial3d(materials.add(StandardMaterial {
+                    base_color: Color::WHITE,
+                    unlit: true,
+                    ..Default::default()
+                })),
+                Transform::from_xyz(0., BALL_RADIUS + 1., 0.),
+            ));
+
+            for team in [team::Team::Red, team::Team::Blue] {
+                commands.spawn((
+                    RoundProp,
+                    BallAim(team),
+                    player::AiAttractor(5.),
+                    player::AttractsTeam(team),
+                    Transform::from_xyz(0., BALL_RADIUS + 1., 0.),
+                ));
+            }
+
+            // red shoots at the +x goal and blue at the -x one
+            for (x, team) in [(300., team::Team::Blue), (-300., team::Team::Red)] {
+                let post_material = materials.add(StandardMaterial {
+                    base_color: team.color(),
+                    unlit: true,
+                    ..Default::default()
+                });
+                for z in [-GOAL_HALF_WIDTH, GOAL_HALF_WIDTH] {
+                    commands.spawn((
+                        RoundProp,
+                        Mesh3d(meshes.add(Cuboid::new(2., 20., 2.))),
+                        MeshMaterial3d(post_material.clone()),
+                        Transform::from_xyz(x, 10., z),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn score_hill(
+    time: Res<Time>,
+    mut scores: ResMut<Scores>,
+    player_query: Query<(&Transform, &player::Player, Option<&team::Team>)>,
+) {
+    for (trans, player, team) in &player_query {
+        if trans.translation.xz().length() < HILL_RADIUS {
+            scores.add(score_key(player, team), time.delta_secs());
+        }
+    }
+}
+
+fn score_knockouts(mut death_events: EventReader<player::GoatDied>, mut scores: ResMut<Scores>) {
+    for event in death_events.read() {
+        let Some(killer) = &event.killer else {
+            continue;
+        };
+        let key = match event.killer_team {
+            Some(team) => team.name().to_owned(),
+            None => killer.clone(),
+        };
+        scores.add(key, 1.);
+    }
+}
+
+fn score_goals(
+    mut scores: ResMut<Scores>,
+    mut ball_query: Query<
+        (&mut Transform, &mut LinearVelocity, &mut AngularVelocity),
+        With<SoccerBall>,
+    >,
+) {
+    for (mut trans, mut linvel, mut angvel) in &mut ball_query {
+        let pos = trans.translation;
+        let in_goal_mouth = pos.z.abs() < GOAL_HALF_WIDTH;
+        if pos.x > 300. && in_goal_mouth {
+            scores.add(team::Team::Red.name().to_owned(), 1.);
+        } else if pos.x < -300. && in_goal_mouth {
+            scores.add(team::Team::Blue.name().to_owned(), 1.);
+        } else if pos.clamp(Vec3::splat(-300.), Vec3::splat(300.)) == pos {
+            continue;
+        }
+
+        // goal or out of bounds, either way kick off again from the middle
+        trans.translation = vec3(0., BALL_RADIUS + 1., 0.);
+        linvel.0 = Vec3::ZERO;
+        angvel.0 = Vec3::ZERO;
+    }
+}
+
+// the goal each team shoots at
+fn attacked_goal(team: team::Team) -> Vec3 {
+    match team {
+        team::Team::Blue => vec3(-300., 0., 0.),
+        _ => vec3(300., 0., 0.),
+    }
+}
+
+fn aim_at_ball(
+    ball_query: Query<&Transform, With<SoccerBall>>,
+    mut aim_query: Query<(&mut Transform, &BallAim), Without<SoccerBall>>,
+) {
+    let Some(ball) = ball_query.iter().next() else {
+        return;
+    };
+    for (mut trans, aim) in &mut aim_query {
+        let away = (ball.translation - attacked_goal(aim.0))
+            .with_y(0.)
+            .normalize_or_zero();
+        trans.translation = ball.translation + away * BALL_AIM_DISTANCE;
+    }
+}
+
+fn end(
+    settings: Res<menu::Settings>,
+    scores: Res<Scores>,
+    round_time: Res<RoundTime>,
+    force_end: Res<ForceEnd>,
+    roster: Res<player::Roster>,
+    name_filter: Res<NameFilter>,
+    player_query: Query<(&player::Player, Option<&team::Team>)>,
+    mut round_result: ResMut<RoundResult>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    let alive = player_query.iter().collect::<Vec<_>>();
+    let mode = scoring_mode(&settings);
+    let team_round = roster.teams() >= 2;
+    let over = match settings.respawn {
+        RespawnMode::Off => mode.is_over(&alive, &scores, round_time.0, team_round),
+        // nobody is out for good so only the score or the clock ends it
+        RespawnMode::Endless => mode.is_won(&scores),
+        RespawnMode::Timed => mode.is_won(&scores) || round_time.0 >= settings.respawn_round_secs,
+    };
+    if force_end.0 || over {
+        *round_result = mode.result(&alive, &scores, &name_filter, team_round);
+        next_game_state.set(GameState::End);
+    }
+}
+
+fn setup_mode_hud(mut commands: Commands) {
+    commands.spawn((
+        ModeHud,
+        Text::new(""),
+        TextFont::default().with_font_size(30.),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.),
+            left: Val::Px(10.),
+            ..Default::default()
+        },
+    ));
+}
+
+fn update_mode_hud(
+    settings: Res<menu::Settings>,
+    scores: Res<Scores>,
+    round_time: Res<RoundTime>,
+    name_filter: Res<NameFilter>,
+    player_query: Query<(&player::Player, Option<&team::Team>)>,
+    mut text_query: Single<&mut Text, With<ModeHud>>,
+) {
+    let alive = player_query.iter().collect::<Vec<_>>();
+    let mode = scoring_mode(&settings);
+    let time_left = time_left(&settings, mode, round_time.0);
+    text_query.0 = mode.hud_text(&alive, &scores, time_left, &name_filter);
+}
+
+fn despawn_mode_hud(mut commands: Commands, hud_query: Query<Entity, With<ModeHud>>) {
+    for hud in hud_query {
+        commands.entity(hud).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scores(scores: &[(&str, f32)]) -> Scores {
+        Scores(
+            scores
+                .iter()
+                .map(|(key, score)| (key.to_string(), *score))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn rounds_end_when_the_last_goats_go_out_together() {
+        for mode in [
+            GameMode::LastStanding,
+            GameMode::KingOfTheHill,
+            GameMode::Knockouts,
+            GameMode::Soccer,
+        ] {
+            assert!(mode.is_over(&[], &Scores::default(), 0., false));
+            assert!(mode.is_over(&[], &Scores::default(), 0., true));
+        }
+        let result =
+            GameMode::LastStanding.result(&[], &Scores::default(), &NameFilter::new(&[]), false);
+        assert_eq!(result.title, "NOBODY WON");
+        assert!(result.winners.is_empty());
+    }
+
+    #[test]
+    fn ties_for_first_are_a_draw() {
+        let name_filter = NameFilter::new(&[]);
+        let result = GameMode::Knockouts.result(
+            &[],
+            &scores(&[("b", 2.), ("a", 2.), ("c", 1.)]),
+            &name_filter,
+            false,
+        );
+        assert_eq!(result.title, "DRAW");
+        assert!(result.winners.is_empty());
+        assert_eq!(result.lines, ["a: 2", "b: 2", "c: 1"]);
+
+        let result =
+            GameMode::Knockouts.result(&[], &scores(&[("b", 2.), ("a", 3.)]), &name_filter, false);
+        assert_eq!(result.title, "a WON");
+        assert_eq!(result.winners, ["a"]);
+    }
+
+    #[test]
+    fn top_scores_are_ordered_by_name_when_tied() {
+        let scores = scores(&[("d", 1.), ("c", 5.), ("b", 1.), ("a", 1.)]);
+        let keys = scores
+            .top(3)
+            .into_iter()
+            .map(|(key, _)| key.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(keys, ["c", "a", "b"]);
+    }
+}
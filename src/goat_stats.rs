@@ -0,0 +1,149 @@
+use crate::{player::GoatSpeed, util};
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+// a normal sized goat weighs this much
+const BASE_MASS: f32 = 100.;
+
+/// where the stats of new goats come from
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum StatsMode {
+    // every goat is the same except for its top speed
+    #[default]
+    Same,
+    // rolled again every time a goat spawns
+    Random,
+    // rolled from the name so a viewer always gets the same goat
+    Viewer,
+}
+
+impl StatsMode {
+    pub fn next(self) -> Self {
+        match self {
+            StatsMode::Same => StatsMode::Random,
+            StatsMode::Random => StatsMode::Viewer,
+            StatsMode::Viewer => StatsMode::Same,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StatsMode::Same => "all the same",
+            StatsMode::Random => "random",
+            StatsMode::Viewer => "same goat every time",
+        }
+    }
+}
+
+/// what a goat was born with, powerups change Speed and ComputedMass but never these.
+/// a big goat redemption is the only thing that grows them
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub struct GoatStats {
+    pub speed: f32,
+    pub mass: f32,
+    // 1 is the size of the model
+    pub scale: f32,
+    // multipliers, 1 is how goats always handled
+    pub turn_rate: f32,
+    pub accel: f32,
+    pub grip: f32,
+}
+
+impl GoatStats {
+    /// stats for a goat called name, bots dont get their own goat so they always roll random ones
+    pub fn roll(mode: StatsMode, name: Option<&str>, goat_speed: &GoatSpeed) -> Self {
+        // stdrng and the std hasher can change between versions, these never do
+        let mut rng = match (mode, name) {
+            (StatsMode::Viewer, Some(name)) => ChaCha8Rng::seed_from_u64(util::fnv1a(name)),
+            _ => ChaCha8Rng::from_os_rng(),
+        };
+        let speed = rng.random_range(goat_speed.min..=goat_speed.max);
+        if mode == StatsMode::Same {
+            return GoatStats {
+                speed,
+                ..Default::default()
+            };
+        }
+
+        let scale = rng.random_range(0.6..1.6);
+        let density = rng.random_range(0.8..1.25);
+        GoatStats {
+            speed,
+            mass: BASE_MASS * scale * scale * density,
+            scale,
+            // big goats are slow to turn
+            turn_rate: rng.random_range(0.7..1.3) / scale.sqrt(),
+            accel: rng.random_range(0.7..1.3),
+            grip: rng.random_range(0.6..1.4),
+        }
+    }
+
+    pub fn size(&self) -> &'static str {
+        match self.scale {
+            s if s < 0.75 => "tiny",
+            s if s < 0.9 => "small",
+            s if s < 1.15 => "regular",
+            s if s < 1.35 => "big",
+            _ => "chonky",
+        }
+    }
+
+    /// one line for the nameplate, nothing when every goat is the same anyway
+    pub fn tag(&self) -> Option<String> {
+        (self.scale != 1.).then(|| format!("{} {:.0}kg", self.size(), self.mass))
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {:.0}kg  speed {:.0}  turning {:.1}  acceleration {:.1}  grip {:.1}",
+            self.size(),
+            self.mass,
+            self.speed,
+            self.turn_rate,
+            self.accel,
+            self.grip
+        )
+    }
+}
+
+impl Default for GoatStats {
+    fn default() -> Self {
+        GoatStats {
+            speed: GoatSpeed::default().min,
+            mass: BASE_MASS,
+            scale: 1.,
+            turn_rate: 1.,
+            accel: 1.,
+            grip: 1.,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn viewers_always_get_the_same_goat() {
+        let goat_speed = GoatSpeed::default();
+        let roll = |name| GoatStats::roll(StatsMode::Viewer, Some(name), &goat_speed);
+        assert_eq!(roll("someone"), roll("someone"));
+        assert_ne!(roll("someone"), roll("someone else"));
+    }
+
+    #[test]
+    fn same_goats_only_differ_in_speed() {
+        let goat_speed = GoatSpeed::default();
+        let stats = GoatStats::roll(StatsMode::Same, Some("someone"), &goat_speed);
+        assert_eq!(
+            stats,
+            GoatStats {
+                speed: stats.speed,
+                ..Default::default()
+            }
+        );
+        assert!((goat_speed.min..=goat_speed.max).contains(&stats.speed));
+    }
+}
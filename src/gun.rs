@@ -1,4 +1,6 @@
-use crate::{player, util, AssetHandles, GameState};
+use crate::{
+    attribution, game_mode, pause, player, powerup, replay, util, AssetHandles, GameState,
+};
 use avian3d::prelude::*;
 use bevy::prelude::*;
 use std::collections::HashMap;
@@ -7,16 +9,34 @@ pub struct GunPlugin;
 
 impl Plugin for GunPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
+        app.insert_resource(Gun::default()).add_systems(
             Update,
-            (shoot, explode).run_if(in_state(GameState::Spectating)),
+            (
+                shoot.run_if(replay::not_replaying.and(pause::not_paused)),
+                explode,
+            )
+                .run_if(in_state(GameState::Spectating)),
         );
     }
 }
 
+/// the streamers weapon, it never runs out
+#[derive(Resource)]
+pub struct Gun {
+    pub weapon: &'static str,
+}
+
+impl Default for Gun {
+    fn default() -> Self {
+        Gun {
+            weapon: "goat launcher",
+        }
+    }
+}
+
 #[derive(Component)]
 #[require(RigidBody = RigidBody::Dynamic, Sensor, CollisionEventsEnabled, Transform, Collider)]
-struct Bullet;
+pub struct Bullet;
 
 fn shoot(
     mut commands: Commands,
@@ -30,20 +50,39 @@ fn shoot(
 
     let trans = camera_pos_query.single().unwrap();
     let bullet_speed = 1000.;
-    commands.spawn((
+    commands.spawn(bullet(
+        &asset_handles,
+        trans.translation + trans.forward() * 0.5,
+        trans.forward() * bullet_speed,
+    ));
+}
+
+/// a bullet that blows up on whatever it touches first
+pub fn bullet(asset_handles: &Res<AssetHandles>, pos: Vec3, velocity: Vec3) -> impl Bundle {
+    (
+        game_mode::RoundProp,
         Bullet,
         Collider::sphere(1.),
-        Transform::from_translation(trans.translation + trans.forward() * 0.5),
-        LinearVelocity(trans.forward() * bullet_speed),
+        Transform::from_translation(pos),
+        LinearVelocity(velocity),
         Mesh3d(asset_handles.the_sphere.clone().unwrap()),
         MeshMaterial3d(asset_handles.bullet_material.clone().unwrap()),
-    ));
+    )
 }
 
 fn explode(
     mut commands: Commands,
     asset_handles: Res<AssetHandles>,
-    mut player_query: Query<(&Transform, &mut LinearVelocity), With<player::Player>>,
+    mut hit_events: EventWriter<attribution::Hit>,
+    mut player_query: Query<
+        (
+            Entity,
+            &Transform,
+            &mut LinearVelocity,
+            Option<&powerup::ActivePowerUp>,
+        ),
+        With<player::Player>,
+    >,
     bullet_query: Query<(Entity, &Transform), With<Bullet>>,
     mut collision_events: EventReader<CollisionStarted>,
 ) {
@@ -62,7 +101,10 @@ fn explode(
         commands.entity(bullet_entity).despawn();
         commands.spawn(util::explosion(&asset_handles, bullet_trans.translation));
 
-        for (player_trans, mut linvel) in &mut player_query {
+        for (player_entity, player_trans, mut linvel, powerup) in &mut player_query {
+            if powerup.is_some_and(|powerup| powerup.is(powerup::PowerUpKind::Shield)) {
+                continue;
+            }
             let dir = (player_trans.translation - bullet_trans.translation).normalize();
             let strength = 1e5
                 / player_trans
@@ -70,6 +112,12 @@ fn explode(
                     .distance_squared(bullet_trans.translation);
 
             linvel.0 += dir * strength;
+
+            hit_events.write(attribution::Hit {
+                victim: player_entity,
+                attacker: attribution::Attacker::Streamer,
+                strength,
+            });
         }
     }
 }
@@ -0,0 +1,279 @@
+use crate::{game_mode, menu, player, GameState};
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+// seconds a goat can stand in lava before it cooks
+pub const LAVA_LETHAL_SECS: f32 = 2.;
+const ROCK_EVERY_SECS: f32 = 5.;
+const ROCK_LIFETIME_SECS: f32 = 15.;
+const ROCK_RADIUS: f32 = 4.;
+
+pub struct HazardPlugin;
+
+impl Plugin for HazardPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HazardLayout(default_layout()))
+            .insert_resource(RockSpawner(Timer::from_seconds(
+                ROCK_EVERY_SECS,
+                TimerMode::Repeating,
+            )))
+            .add_systems(
+                OnEnter(GameState::Spectating),
+                spawn_hazards.run_if(hazards_enabled),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    drop_into_pits,
+                    burn_in_lava,
+                    (drop_rocks, despawn_old_rocks),
+                )
+                    .run_if(in_state(GameState::Spectating).and(hazards_enabled)),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum HazardKind {
+    // hole in the ground, goats fall through the floor and out of bounds
+    Pit { radius: f32 },
+    Bumper { radius: f32 },
+    Lava { radius: f32 },
+    // bar spinning around its middle
+    Sweeper { length: f32, speed: f32 },
+}
+
+/// one hazard in a map layout
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct HazardSpawn {
+    pub kind: HazardKind,
+    pub x: f32,
+    pub z: f32,
+}
+
+/// the hazards of the map, hazard_layout in config.ron
+#[derive(Resource, Clone)]
+pub struct HazardLayout(pub Vec<HazardSpawn>);
+
+/// the hazards used when theres no map to take them from
+pub fn default_layout() -> Vec<HazardSpawn> {
+    let mut layout = vec![HazardSpawn {
+        kind: HazardKind::Sweeper {
+            length: 120.,
+            speed: 0.6,
+        },
+        x: 0.,
+        z: 0.,
+    }];
+    for (x, z) in [(1., 1.), (1., -1.), (-1., 1.), (-1., -1.)] {
+        layout.push(HazardSpawn {
+            kind: HazardKind::Pit { radius: 20. },
+            x: x * 150.,
+            z: z * 150.,
+        });
+        layout.push(HazardSpawn {
+            kind: HazardKind::Bumper { radius: 6. },
+            x: x * 80.,
+            z: z * 80.,
+        });
+    }
+    for (x, z) in [(0., 200.), (0., -200.), (200., 0.), (-200., 0.)] {
+        layout.push(HazardSpawn {
+            kind: HazardKind::Lava { radius: 25. },
+            x,
+            z,
+        });
+    }
+    layout
+}
+
+fn hazards_enabled(settings: Res<menu::Settings>) -> bool {
+    settings.hazards
+}
+
+#[derive(Component)]
+#[require(Sensor, CollidingEntities)]
+struct Pit;
+
+#[derive(Component)]
+#[require(Sensor, CollidingEntities)]
+struct Lava;
+
+/// how long a goat has been in lava without getting out
+#[derive(Component, Default)]
+pub struct Burn(pub f32);
+
+#[derive(Component)]
+struct Rock(Timer);
+
+#[derive(Resource)]
+struct RockSpawner(Timer);
+
+fn spawn_hazards(
+    mut commands: Commands,
+    layout: Res<HazardLayout>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for spawn in &layout.0 {
+        spawn_hazard(&mut commands, &mut meshes, &mut materials, *spawn);
+    }
+}
+
+fn spawn_hazard(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    spawn: HazardSpawn,
+) {
+    let color_material = |materials: &mut ResMut<Assets<StandardMaterial>>, color: Color| {
+        materials.add(StandardMaterial {
+            base_color: color,
+            unlit: true,
+            ..Default::default()
+        })
+    };
+    let pos = vec3(spawn.x, 0., spawn.z);
+
+    match spawn.kind {
+        HazardKind::Pit { radius } => {
+            commands.spawn((
+                game_mode::RoundProp,
+                Pit,
+                Collider::cylinder(radius, 2.),
+                Mesh3d(meshes.add(Cylinder::new(radius, 0.2))),
+                MeshMaterial3d(color_material(materials, Color::BLACK)),
+                Transform::from_translation(pos + Vec3::Y * 0.6),
+            ));
+        }
+        HazardKind::Bumper { radius } => {
+            commands.spawn((
+                game_mode::RoundProp,
+                RigidBody::Static,
+                Collider::cylinder(radius, 6.),
+                Restitution::new(1.5).with_combine_rule(CoefficientCombine::Max),
+                Mesh3d(meshes.add(Cylinder::new(radius, 6.))),
+                MeshMaterial3d(color_material(materials, Color::srgb(1., 0.3, 0.8))),
+                Transform::from_translation(pos + Vec3::Y * 3.5),
+            ));
+        }
+        HazardKind::Lava { radius } => {
+            commands.spawn((
+                game_mode::RoundProp,
+                Lava,
+                Collider::cylinder(radius, 2.),
+                Mesh3d(meshes.add(Cylinder::new(radius, 0.2))),
+                MeshMaterial3d(color_material(materials, Color::srgb(1., 0.35, 0.))),
+                Transform::from_translation(pos + Vec3::Y * 0.6),
+            ));
+        }
+        HazardKind::Sweeper { length, speed } => {
+            commands.spawn((
+                game_mode::RoundProp,
+                RigidBody::Kinematic,
+                Collider::cuboid(length, 3., 3.),
+                AngularVelocity(Vec3::Y * speed),
+                Mesh3d(meshes.add(Cuboid::new(length, 3., 3.))),
+                MeshMaterial3d(color_material(materials, Color::srgb(0.3, 0.3, 0.35))),
+                Transform::from_translation(pos + Vec3::Y * 2.),
+            ));
+        }
+    }
+}
+
+// turning the collider off makes the goat fall through the floor until kill_players gets it
+fn drop_into_pits(
+    mut commands: Commands,
+    pit_query: Query<&CollidingEntities, With<Pit>>,
+    player_query: Query<(), With<player::Player>>,
+) {
+    for colliding in &pit_query {
+        for entity in colliding.iter() {
+            if player_query.contains(*entity) {
+                commands.entity(*entity).insert(ColliderDisabled);
+            }
+        }
+    }
+}
+
+fn burn_in_lava(
+    mut commands: Commands,
+    time: Res<Time>,
+    lava_query: Query<&CollidingEntities, With<Lava>>,
+    mut player_query: Query<(Entity, Option<&mut Burn>), With<player::Player>>,
+) {
+    let in_lava = lava_query
+        .iter()
+        .flat_map(|colliding| colliding.iter().copied())
+        .collect::<HashSet<_>>();
+    for (goat, burn) in &mut player_query {
+        match (burn, in_lava.contains(&goat)) {
+            (Some(mut burn), true) => burn.0 += time.delta_secs(),
+            (None, true) => {
+                commands.entity(goat).insert(Burn(time.delta_secs()));
+            }
+            // getting out cools the goat off again
+            (Some(_), false) => {
+                commands.entity(goat).remove::<Burn>();
+            }
+            (None, false) => {}
+        }
+    }
+}
+
+fn drop_rocks(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut spawner: ResMut<RockSpawner>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut rock_handles: Local<Option<(Handle<Mesh>, Handle<StandardMaterial>)>>,
+) {
+    spawner.0.tick(time.delta());
+    if !spawner.0.just_finished() {
+        return;
+    }
+
+    let (mesh, material) = rock_handles
+        .get_or_insert_with(|| {
+            (
+                meshes.add(Sphere::new(ROCK_RADIUS)),
+                materials.add(StandardMaterial {
+                    base_color: Color::srgb(0.4, 0.4, 0.4),
+                    unlit: true,
+                    ..Default::default()
+                }),
+            )
+        })
+        .clone();
+
+    let pos = vec3(
+        rand::random_range(-280.0..280.0),
+        150.,
+        rand::random_range(-280.0..280.0),
+    );
+    commands.spawn((
+        game_mode::RoundProp,
+        Rock(Timer::from_seconds(ROCK_LIFETIME_SECS, TimerMode::Once)),
+        RigidBody::Dynamic,
+        Collider::sphere(ROCK_RADIUS),
+        ComputedMass::new(300.),
+        Mesh3d(mesh),
+        MeshMaterial3d(material),
+        Transform::from_translation(pos),
+    ));
+}
+
+fn despawn_old_rocks(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut rock_query: Query<(Entity, &mut Rock)>,
+) {
+    for (entity, mut rock) in &mut rock_query {
+        if rock.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
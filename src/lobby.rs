@@ -0,0 +1,704 @@
+use crate::{
+    game_mode, menu, moderation, pause, player, spawn::SpawnPlanner, twitch, AssetHandles,
+    GameState,
+};
+use bevy::color::palettes::basic;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+pub const BAN_FILE: &str = "banned.txt";
+
+// rows in the list before it just says how many more there are
+const LIST_SIZE: usize = 15;
+const COUNTDOWN_SECS: f32 = 5.;
+// how many bots fill with bots adds when theres no goat cap
+const BOT_FILL: u32 = 10;
+// the lobby never starts a round by itself with fewer goats than this
+const MIN_GOATS: usize = 2;
+
+pub struct LobbyPlugin;
+
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BanList::load(Path::new(BAN_FILE)))
+            .insert_resource(JoinQueue::default())
+            .insert_resource(RoundHistory::default())
+            .insert_resource(Lobby::default())
+            .add_systems(
+                OnEnter(GameState::Connected),
+                (reset_lobby, setup_lobby, fill_from_queue),
+            )
+            .add_systems(OnEnter(GameState::Spectating), remember_players)
+            .add_systems(OnExit(GameState::End), clear_field)
+            .add_systems(
+                Update,
+                (
+                    lobby_buttons,
+                    update_lobby_list,
+                    auto_start,
+                    update_lobby_header,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Connected)),
+            )
+            .add_systems(OnExit(GameState::Connected), despawn_lobby);
+    }
+}
+
+/// viewers that cant join anymore, kept in banned.txt one name per line.
+/// names are kept lowercase like twitch logins, so go through insert and contains
+#[derive(Resource, Default)]
+pub struct BanList(HashSet<String>);
+
+impl BanList {
+    pub fn insert(&mut self, viewer: &str) {
+        self.0.insert(viewer.to_lowercase());
+    }
+
+    pub fn remove(&mut self, viewer: &str) {
+        self.0.remove(&viewer.to_lowercase());
+    }
+
+    pub fn contains(&self, viewer: &str) -> bool {
+        self.0.contains(&viewer.to_lowercase())
+    }
+
+    pub fn load(path: &Path) -> Self {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return BanList::default();
+        };
+        BanList(
+            text.lines()
+                .map(|line| line.trim().to_lowercase())
+                .filter(|line| !line.is_empty())
+                .collect(),
+        )
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut names = self.0.iter().cloned().collect::<Vec<_>>();
+        names.sort();
+        std::fs::write(path, names.join("\n") + "\n")
+    }
+}
+
+/// who gets in first when more people want to play than fit
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum JoinSelection {
+    // in the order they asked
+    #[default]
+    Queue,
+    // random, but whoever sat out the most rounds is the most likely
+    Weighted,
+}
+
+impl JoinSelection {
+    pub fn next(self) -> Self {
+        match self {
+            JoinSelection::Queue => JoinSelection::Weighted,
+            JoinSelection::Weighted => JoinSelection::Queue,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            JoinSelection::Queue => "queue",
+            JoinSelection::Weighted => "random, favor new players",
+        }
+    }
+}
+
+/// people that asked to play when the field was full
+#[derive(Resource, Default)]
+pub struct JoinQueue(pub Vec<twitch::Message>);
+
+impl JoinQueue {
+    pub fn push(&mut self, msg: twitch::Message) {
+        if !self.0.iter().any(|queued| queued.sender == msg.sender) {
+            self.0.push(msg);
+        }
+    }
+
+    // everyone in the order theyll get in
+    fn ordered(&self, settings: &menu::Settings, history: &RoundHistory) -> Vec<twitch::Message> {
+        let mut queue = match settings.join_selection {
+            JoinSelection::Queue => self.0.clone(),
+            JoinSelection::Weighted => {
+                // weighted shuffle, a bigger weight means a bigger key more often
+                let mut keyed = self
+                    .0
+                    .iter()
+                    .map(|msg| {
+                        let key = rand::random::<f32>().powf(1. / history.weight(&msg.sender));
+                        (key, msg.clone())
+                    })
+                    .collect::<Vec<_>>();
+                keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+                keyed.into_iter().map(|(_, msg)| msg).collect()
+            }
+        };
+        if settings.sub_priority {
+            // stable so subs keep their order among themselves
+            queue.sort_by_key(|msg| !msg.is_sub());
+        }
+        queue
+    }
+}
+
+// which round every viewer last played in
+#[derive(Resource, Default)]
+struct RoundHistory {
+    rounds: u32,
+    last_played: HashMap<String, u32>,
+}
+
+impl RoundHistory {
+    // 1 for someone who played last round, more for every round they sat out
+    fn weight(&self, viewer: &str) -> f32 {
+        let last = self.last_played.get(viewer).copied().unwrap_or(0);
+        (self.rounds + 1 - last) as f32
+    }
+}
+
+#[derive(Resource, Default)]
+struct Lobby {
+    open_secs: f32,
+    // seconds left before the round starts by itself
+    countdown: Option<f32>,
+}
+
+impl Lobby {
+    // true once the countdown ran out and the round should start
+    fn tick(&mut self, settings: &menu::Settings, goats: usize, dt: f32) -> bool {
+        self.open_secs += dt;
+
+        if goats < MIN_GOATS {
+            self.countdown = None;
+            return false;
+        }
+        let enough_goats = settings
+            .auto_start_goats
+            .is_some_and(|wanted| goats as u32 >= wanted);
+        let waited = settings
+            .auto_start_secs
+            .is_some_and(|secs| self.open_secs >= secs);
+        if self.countdown.is_none() && (enough_goats || waited) {
+            self.countdown = Some(COUNTDOWN_SECS);
+        }
+
+        let Some(countdown) = &mut self.countdown else {
+            return false;
+        };
+        *countdown -= dt;
+        *countdown <= 0.
+    }
+}
+
+#[derive(Component)]
+struct LobbyPanel;
+
+#[derive(Component)]
+struct LobbyHeader;
+
+#[derive(Component)]
+struct LobbyList;
+
+#[derive(Component, PartialEq, Eq)]
+#[require(Button)]
+enum LobbyButton {
+    Kick(String),
+    Ban(String),
+    FillBots,
+    Start,
+}
+
+// a viewer and how many goats they have, bots are grouped into one row
+#[derive(PartialEq, Eq, Clone)]
+struct LobbyRow {
+    name: String,
+    goats: usize,
+    bot: bool,
+}
+
+/// takes every goat of a viewer off the field, or all the bots
+pub fn kick(
+    commands: &mut Commands,
+    players: &mut player::Players,
+    player_query: &Query<(Entity, &player::Player, Has<player::Bot>)>,
+    viewer: &str,
+    bots: bool,
+) {
+    for (entity, player, is_bot) in player_query {
+        if is_bot != bots || !player.is_owned_by(viewer) {
+            continue;
+        }
+        players.0.remove(player.name());
+        commands.entity(entity).despawn();
+    }
+}
+
+fn reset_lobby(mut lobby: ResMut<Lobby>) {
+    *lobby = Lobby::default();
+}
+
+// the people left waiting last round get the first spots
+fn fill_from_queue(
+    mut commands: Commands,
+    settings: Res<menu::Settings>,
+    asset_handles: Res<AssetHandles>,
+    goat_speed: Res<player::GoatSpeed>,
+    history: Res<RoundHistory>,
+    mut players: ResMut<player::Players>,
+    mut queue: ResMut<JoinQueue>,
+    planner: SpawnPlanner,
+    mut join_events: EventWriter<player::GoatJoined>,
+    goat_query: Query<&Transform, With<player::Player>>,
+) {
+    let mut taken = goat_query
+        .iter()
+        .map(|trans| trans.translation)
+        .collect::<Vec<_>>();
+    for msg in queue.ordered(&settings, &history) {
+        if player::is_full(&settings, &players) {
+            break;
+        }
+        if let Some(joined) = player::spawn_viewer(
+            &mut commands,
+            &asset_handles,
+            &settings,
+            &mut players,
+            &goat_speed,
+            &planner,
+            &mut taken,
+            &msg,
+            false,
+        ) {
+            join_events.write(joined);
+        }
+        queue.0.retain(|queued| queued.sender != msg.sender);
+    }
+}
+
+fn remember_players(
+    mut history: ResMut<RoundHistory>,
+    player_query: Query<&player::Player, Without<player::Bot>>,
+) {
+    history.rounds += 1;
+    let round = history.rounds;
+    for player in &player_query {
+        history
+            .last_played
+            .insert(player.viewer().to_owned(), round);
+    }
+}
+
+// the next round starts on an empty field
+fn clear_field(
+    mut commands: Commands,
+    mut players: ResMut<player::Players>,
+    round_query: Query<Entity, Or<(With<player::Player>, With<game_mode::RoundProp>)>>,
+) {
+    for entity in &round_query {
+        commands.entity(entity).despawn();
+    }
+    players.0.clear();
+}
+
+fn small_button(action: LobbyButton, text: &str) -> impl Bundle {
+    (
+        action,
+        Node {
+            padding: UiRect::axes(Val::Px(8.), Val::Px(2.)),
+            border: UiRect::all(Val::Px(2.)),
+            ..Default::default()
+        },
+        BorderColor(basic::BLACK.into()),
+        BorderRadius::all(Val::Px(4.)),
+        BackgroundColor(basic::GRAY.into()),
+        children![(Text::new(text), TextFont::default().with_font_size(16.))],
+    )
+}
+
+fn setup_lobby(mut commands: Commands) {
+    commands.spawn((
+        LobbyPanel,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(25.),
+            right: Val::Percent(2.),
+            min_width: Val::Px(320.),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(8.),
+            padding: UiRect::all(Val::Px(10.)),
+            ..Default::default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.4)),
+        BorderRadius::all(Val::Px(8.)),
+        children![
+            (
+                LobbyHeader,
+                Text::new("LOBBY"),
+                TextFont::default().with_font_size(26.),
+            ),
+            (
+                LobbyList,
+                Node {
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.),
+                    ..Default::default()
+                },
+            ),
+            menu::button(LobbyButton::FillBots, "FILL WITH BOTS", Val::Px(300.)),
+            menu::button(LobbyButton::Start, "START ROUND", Val::Px(300.)),
+        ],
+    ));
+}
+
+fn lobby_buttons(
+    mut commands: Commands,
+    settings: Res<menu::Settings>,
+    asset_handles: Res<AssetHandles>,
+    goat_speed: Res<player::GoatSpeed>,
+    mut players: ResMut<player::Players>,
+    mut join_events: EventWriter<player::GoatJoined>,
+    mut mod_events: EventWriter<moderation::ModAction>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    planner: SpawnPlanner,
+    player_query: Query<(Entity, &player::Player, Has<player::Bot>)>,
+    goat_query: Query<&Transform, With<player::Player>>,
+    mut button_query: Query<
+        (&Interaction, &LobbyButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+) {
+    for (interaction, action, mut color) in &mut button_query {
+        match *interaction {
+            Interaction::Pressed => match action {
+                LobbyButton::Kick(viewer) => {
+                    // the bots row is the only one named after bots
+                    let bots = player_query
+                        .iter()
+                        .any(|(_, player, is_bot)| is_bot && player.viewer() == viewer);
+                    if bots {
+                        kick(&mut commands, &mut players, &player_query, viewer, true);
+                    } else {
+                        mod_events.write(moderation::ModAction {
+                            by: moderation::STREAMER.to_owned(),
+                            command: moderation::ModCommand::Kick(viewer.clone()),
+                        });
+                    }
+                }
+                LobbyButton::Ban(viewer) => {
+                    mod_events.write(moderation::ModAction {
+                        by: moderation::STREAMER.to_owned(),
+                        command: moderation::ModCommand::Ban(viewer.clone()),
+                    });
+                }
+                LobbyButton::FillBots => {
+                    let target = settings
+                        .max_goats
+                        .unwrap_or(players.0.len() as u32 + BOT_FILL);
+                    let mut taken = goat_query
+                        .iter()
+                        .map(|trans| trans.translation)
+                        .collect::<Vec<_>>();
+                    while (players.0.len() as u32) < target {
+                        join_events.write(player::spawn_bot(
+                            &mut commands,
+                            &asset_handles,
+                            &settings,
+                            &mut players,
+                            &goat_speed,
+                            &planner,
+                            &mut taken,
+                            "mrrow",
+                            false,
+                        ));
+                    }
+                }
+                LobbyButton::Start => {
+                    next_game_state.set(GameState::Spectating);
+                }
+            },
+            Interaction::Hovered => {
+                *color = basic::BLACK.into();
+            }
+            Interaction::None => {
+                *color = basic::GRAY.into();
+            }
+        }
+    }
+}
+
+// rebuilds the rows whenever someone joins or leaves
+fn update_lobby_list(
+    mut commands: Commands,
+    name_filter: Res<moderation::NameFilter>,
+    player_query: Query<(&player::Player, Has<player::Bot>)>,
+    list: Single<Entity, With<LobbyList>>,
+    // the list the rows were built in and what they say
+    mut shown: Local<(Option<Entity>, Vec<LobbyRow>)>,
+) {
+    let mut viewers = BTreeMap::<(bool, &str), usize>::new();
+    for (player, is_bot) in &player_query {
+        *viewers.entry((is_bot, player.viewer())).or_default() += 1;
+    }
+    let rows = viewers
+        .into_iter()
+        .map(|((bot, name), goats)| LobbyRow {
+            name: name.to_owned(),
+            goats,
+            bot,
+        })
+        .collect::<Vec<_>>();
+    let list = *list;
+    if shown.0 == Some(list) && shown.1 == rows {
+        return;
+    }
+
+    commands.entity(list).despawn_related::<Children>();
+    for row in rows.iter().take(LIST_SIZE) {
+        let label = match (row.bot, row.goats) {
+            (true, goats) => format!("{goats} bots"),
+            (false, 1) => name_filter.display(&row.name),
+            (false, goats) => format!("{} x{goats}", name_filter.display(&row.name)),
+        };
+        let mut entity = commands.spawn((
+            ChildOf(list),
+            Node {
+                justify_content: JustifyContent::SpaceBetween,
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(6.),
+                ..Default::default()
+            },
+            children![(Text::new(label), TextFont::default().with_font_size(20.))],
+        ));
+        entity.with_child(small_button(LobbyButton::Kick(row.name.clone()), "KICK"));
+        if !row.bot {
+            entity.with_child(small_button(LobbyButton::Ban(row.name.clone()), "BAN"));
+        }
+    }
+    if rows.len() > LIST_SIZE {
+        commands.spawn((
+            ChildOf(list),
+            Text::new(format!("and {} more", rows.len() - LIST_SIZE)),
+            TextFont::default().with_font_size(20.),
+        ));
+    }
+    *shown = (Some(list), rows);
+}
+
+fn auto_start(
+    time: Res<Time>,
+    settings: Res<menu::Settings>,
+    mut lobby: ResMut<Lobby>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    player_query: Query<(), With<player::Player>>,
+) {
+    let goats = player_query.iter().count();
+    if lobby.tick(&settings, goats, time.delta_secs()) {
+        next_game_state.set(GameState::Spectating);
+    }
+}
+
+fn update_lobby_header(
+    settings: Res<menu::Settings>,
+    lobby: Res<Lobby>,
+    queue: Res<JoinQueue>,
+    player_query: Query<(), With<player::Player>>,
+    mut header: Single<&mut Text, With<LobbyHeader>>,
+) {
+    let goats = player_query.iter().count();
+    let mut text = match settings.max_goats {
+        Some(max) => format!("LOBBY  {goats} / {max} goats"),
+        None => format!("LOBBY  {goats} goats"),
+    };
+    if !queue.0.is_empty() {
+        text += &format!("\n{} waiting for the next round", queue.0.len());
+    }
+    match (
+        lobby.countdown,
+        settings.auto_start_goats,
+        settings.auto_start_secs,
+    ) {
+        (Some(countdown), _, _) => text += &format!("\nstarting in {}", countdown.ceil() as u32),
+        (None, Some(wanted), _) if goats < wanted as usize => {
+            text += &format!("\nstarts at {wanted} goats")
+        }
+        (None, _, Some(secs)) if lobby.open_secs < secs => {
+            text += &format!("\nstarts in {}s", (secs - lobby.open_secs).ceil() as u32)
+        }
+        _ => {}
+    }
+    if header.0 != text {
+        header.0 = text;
+    }
+}
+
+fn despawn_lobby(mut commands: Commands, panel_query: Query<Entity, With<LobbyPanel>>) {
+    for panel in &panel_query {
+        commands.entity(panel).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bans_survive_a_restart_whatever_the_case() {
+        let path = std::env::temp_dir().join(format!("banned-{}.txt", std::process::id()));
+        let mut ban_list = BanList::default();
+        // the lobby ban button and !ban can both hand in mixed case names
+        ban_list.insert("MixedCase");
+        ban_list.insert("lowercase");
+        ban_list.save(&path).unwrap();
+
+        let mut loaded = BanList::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.contains("mixedcase"));
+        assert!(loaded.contains("MIXEDCASE"));
+        assert!(loaded.contains("LowerCase"));
+        assert!(!loaded.contains("someone"));
+
+        loaded.remove("MIXEDcase");
+        assert!(!loaded.contains("mixedcase"));
+    }
+
+    fn message(sender: &str, sub: bool) -> twitch::Message {
+        twitch::Message {
+            sender: sender.to_owned(),
+            display_name: sender.to_owned(),
+            text: "!play".to_owned(),
+            badges: if sub {
+                vec!["subscriber".to_owned()]
+            } else {
+                vec![]
+            },
+            reward: None,
+            bits: 0,
+        }
+    }
+
+    fn senders(queue: &[twitch::Message]) -> Vec<&str> {
+        queue.iter().map(|msg| msg.sender.as_str()).collect()
+    }
+
+    #[test]
+    fn nobody_queues_twice() {
+        let mut queue = JoinQueue::default();
+        queue.push(message("a", false));
+        queue.push(message("b", false));
+        queue.push(message("a", false));
+        assert_eq!(senders(&queue.0), ["a", "b"]);
+    }
+
+    #[test]
+    fn queue_keeps_the_order_people_asked_in() {
+        let mut queue = JoinQueue::default();
+        for sender in ["c", "a", "b"] {
+            queue.push(message(sender, false));
+        }
+        let ordered = queue.ordered(&menu::Settings::default(), &RoundHistory::default());
+        assert_eq!(senders(&ordered), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn subs_skip_the_queue_in_their_own_order() {
+        let settings = menu::Settings {
+            sub_priority: true,
+            ..Default::default()
+        };
+        let mut queue = JoinQueue::default();
+        queue.push(message("a", false));
+        queue.push(message("sub1", true));
+        queue.push(message("b", false));
+        queue.push(message("sub2", true));
+        let ordered = queue.ordered(&settings, &RoundHistory::default());
+        assert_eq!(senders(&ordered), ["sub1", "sub2", "a", "b"]);
+    }
+
+    #[test]
+    fn sitting_out_rounds_raises_the_weight() {
+        let history = RoundHistory {
+            rounds: 10,
+            last_played: HashMap::from([("regular".to_owned(), 10), ("old".to_owned(), 4)]),
+        };
+        assert_eq!(history.weight("regular"), 1.);
+        assert_eq!(history.weight("old"), 7.);
+        assert_eq!(history.weight("new"), 11.);
+    }
+
+    #[test]
+    fn weighted_selection_favors_new_players() {
+        let settings = menu::Settings {
+            join_selection: JoinSelection::Weighted,
+            ..Default::default()
+        };
+        let history = RoundHistory {
+            rounds: 10,
+            last_played: HashMap::from([("regular".to_owned(), 10)]),
+        };
+        let mut queue = JoinQueue::default();
+        queue.push(message("regular", false));
+        queue.push(message("new", false));
+
+        // new has 11 times the weight so gets in first about 11 out of 12 times
+        let new_first = (0..2000)
+            .filter(|_| queue.ordered(&settings, &history)[0].sender == "new")
+            .count();
+        assert!(new_first > 1600, "new went first {new_first} times");
+        assert!(new_first < 2000, "regular never went first");
+    }
+
+    #[test]
+    fn lobby_waits_for_enough_goats() {
+        let settings = menu::Settings {
+            auto_start_goats: Some(4),
+            ..Default::default()
+        };
+        let mut lobby = Lobby::default();
+        assert!(!lobby.tick(&settings, 3, 60.));
+        assert_eq!(lobby.countdown, None);
+        assert!(!lobby.tick(&settings, 4, 1.));
+        assert_eq!(lobby.countdown, Some(COUNTDOWN_SECS - 1.));
+        assert!(lobby.tick(&settings, 4, COUNTDOWN_SECS));
+    }
+
+    #[test]
+    fn lobby_starts_after_waiting_long_enough() {
+        let settings = menu::Settings {
+            auto_start_secs: Some(30.),
+            ..Default::default()
+        };
+        let mut lobby = Lobby::default();
+        assert!(!lobby.tick(&settings, 2, 29.));
+        assert_eq!(lobby.countdown, None);
+        assert!(!lobby.tick(&settings, 2, 1.));
+        assert!(lobby.countdown.is_some());
+        assert!(lobby.tick(&settings, 2, COUNTDOWN_SECS));
+    }
+
+    #[test]
+    fn countdown_stops_when_goats_leave() {
+        let settings = menu::Settings {
+            auto_start_goats: Some(2),
+            ..Default::default()
+        };
+        let mut lobby = Lobby::default();
+        lobby.tick(&settings, 2, 1.);
+        assert!(lobby.countdown.is_some());
+        // one goat is never enough, whatever the settings say
+        assert!(!lobby.tick(&settings, 1, COUNTDOWN_SECS));
+        assert_eq!(lobby.countdown, None);
+    }
+
+    #[test]
+    fn lobby_never_starts_by_itself_when_auto_start_is_off() {
+        let mut lobby = Lobby::default();
+        assert!(!lobby.tick(&menu::Settings::default(), 50, 600.));
+        assert_eq!(lobby.countdown, None);
+    }
+}
@@ -1,9 +1,33 @@
+mod attribution;
+mod chat_control;
+mod config;
+mod director;
+mod feed;
+mod follow_camera;
+mod game_mode;
+mod goat_stats;
 mod gun;
+mod hazard;
+mod lobby;
 mod menu;
+mod menu_camera;
+mod moderation;
+mod pause;
+mod playback;
 mod player;
+mod powerup;
+mod recording;
+mod redeem;
+mod replay;
+mod respawn;
+mod scoreboard;
+mod spawn;
 mod spectator_camera;
+mod team;
+mod text_input;
 mod twitch;
 mod util;
+mod viewer_stats;
 
 use avian3d::prelude::*;
 use bevy::core_pipeline::Skybox;
@@ -39,6 +63,33 @@ fn main() {
             player::PlayerPlugin,
             spectator_camera::SpectatorCameraPlugin,
             gun::GunPlugin,
+            chat_control::ChatControlPlugin,
+            team::TeamPlugin,
+            game_mode::GameModePlugin,
+            powerup::PowerUpPlugin,
+            hazard::HazardPlugin,
+        ))
+        .add_plugins((
+            director::DirectorPlugin,
+            follow_camera::FollowCameraPlugin,
+            menu_camera::MenuCameraPlugin,
+            replay::ReplayPlugin,
+            recording::RecordingPlugin,
+            playback::PlaybackPlugin,
+            feed::FeedPlugin,
+            scoreboard::ScoreboardPlugin,
+            attribution::AttributionPlugin,
+            viewer_stats::ViewerStatsPlugin,
+            config::ConfigPlugin,
+            text_input::TextInputPlugin,
+            pause::PausePlugin,
+            lobby::LobbyPlugin,
+            moderation::ModerationPlugin,
+        ))
+        .add_plugins((
+            respawn::RespawnPlugin,
+            spawn::SpawnPlugin,
+            redeem::RedeemPlugin,
         ))
         .insert_resource(Gravity(Vec3::NEG_Y * 50.))
         .insert_resource(AssetHandles::default())
@@ -47,6 +98,9 @@ fn main() {
         .add_systems(Startup, setup)
         .add_systems(Update, setup_skybox.run_if(should_run_skybox))
         .add_systems(OnEnter(GameState::Spectating), lock_cursor)
+        .add_systems(OnEnter(GameState::Playback), lock_cursor)
+        .add_systems(OnExit(GameState::Playback), release_cursor)
+        .add_systems(OnEnter(GameState::End), release_cursor)
         .run();
 }
 
@@ -56,7 +110,11 @@ enum GameState {
     Start,
     Connected,
     Spectating,
+    // the round is cleared out and set up again, then its straight back to Spectating
+    Restarting,
     End,
+    // watching a recorded round
+    Playback,
 }
 
 #[derive(Resource, Default)]
@@ -70,6 +128,7 @@ struct AssetHandles {
     explosion_material: Option<Handle<StandardMaterial>>,
     skybox: Option<Handle<Image>>,
     bullet_material: Option<Handle<StandardMaterial>>,
+    team_materials: std::collections::HashMap<team::Team, Handle<StandardMaterial>>,
 }
 
 fn setup(
@@ -142,6 +201,17 @@ fn setup(
         unlit: true,
         ..Default::default()
     }));
+    for team in team::Team::ALL {
+        asset_handles.team_materials.insert(
+            team,
+            materials.add(StandardMaterial {
+                base_color: team.color(),
+                base_color_texture: Some(asset_server.load("goat/goat.png")),
+                unlit: true,
+                ..Default::default()
+            }),
+        );
+    }
     asset_handles.the_sphere = Some(meshes.add(Sphere::new(1.25)));
     asset_handles.explosion_sound = Some(asset_server.load("explosion.ogg"));
     asset_handles.explosion_cube = Some(meshes.add(Cuboid::from_size(Vec3::splat(10.))));
@@ -191,10 +261,22 @@ fn setup_skybox(
     skybox_loaded.0 = true;
 }
 
-fn lock_cursor(mut window: Single<&mut Window>) {
-    window.cursor_options = bevy::window::CursorOptions {
-        visible: false,
-        grab_mode: bevy::window::CursorGrabMode::Locked,
+fn cursor_options(locked: bool) -> bevy::window::CursorOptions {
+    bevy::window::CursorOptions {
+        visible: !locked,
+        grab_mode: if locked {
+            bevy::window::CursorGrabMode::Locked
+        } else {
+            bevy::window::CursorGrabMode::None
+        },
         hit_test: true,
-    };
+    }
+}
+
+fn lock_cursor(mut window: Single<&mut Window>) {
+    window.cursor_options = cursor_options(true);
+}
+
+fn release_cursor(mut window: Single<&mut Window>) {
+    window.cursor_options = cursor_options(false);
 }
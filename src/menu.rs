@@ -1,4 +1,8 @@
-use crate::{player, twitch, util, GameState};
+use crate::{
+    chat_control, config, game_mode, goat_stats, lobby, recording, replay, respawn, spawn, team,
+    text_input::{self, TextInput, TextRule},
+    twitch, viewer_stats, GameState,
+};
 use bevy::color::palettes::basic;
 use bevy::prelude::*;
 
@@ -15,12 +19,24 @@ impl Plugin for MenuPlugin {
                     button_system,
                     update_filter_text,
                     update_gpp_text,
-                    (update_name, update_name_text).run_if(in_state(GameState::Start)),
+                    update_control_text,
+                    update_team_text,
+                    update_mode_text,
+                    update_hazard_text,
+                    update_selection_text,
+                    update_sub_priority_text,
+                    update_late_join_text,
+                    update_respawn_text,
+                    update_spawn_text,
+                    update_stats_text,
+                    (sync_name_input, update_name, submit_name).run_if(in_state(GameState::Start)),
                 ),
             )
             .add_systems(OnEnter(GameState::Connected), update_menu)
             .add_systems(OnEnter(GameState::Spectating), despawn_main_menu)
-            .add_systems(OnEnter(GameState::End), setup_end_menu);
+            .add_systems(OnEnter(GameState::Playback), despawn_main_menu)
+            .add_systems(OnEnter(GameState::End), setup_end_menu)
+            .add_systems(OnExit(GameState::End), despawn_main_menu);
     }
 }
 
@@ -28,6 +44,31 @@ impl Plugin for MenuPlugin {
 pub struct Settings {
     pub filter_joins: bool,
     pub goats_per_player: u32,
+    pub control_mode: chat_control::ControlMode,
+    pub democracy_interval: f32,
+    pub team_mode: team::TeamMode,
+    pub game_mode: game_mode::GameMode,
+    pub hazards: bool,
+    // joins past this many goats are turned away
+    pub max_goats: Option<u32>,
+    // the lobby counts down to the round once either of these is reached
+    pub auto_start_goats: Option<u32>,
+    pub auto_start_secs: Option<f32>,
+    // who gets the spots when the field is full
+    pub join_selection: lobby::JoinSelection,
+    pub sub_priority: bool,
+    // joins keep coming in after the round started
+    pub late_joins: bool,
+    pub respawn: respawn::RespawnMode,
+    // seconds a goat stays dead, and how long timed respawn rounds are
+    pub respawn_secs: f32,
+    pub respawn_round_secs: f32,
+    pub spawn_layout: spawn::SpawnLayout,
+    pub spawn_facing: spawn::SpawnFacing,
+    // how far from the fence goats spawn and how far apart they try to be
+    pub spawn_margin: f32,
+    pub spawn_spacing: f32,
+    pub goat_stats: goat_stats::StatsMode,
 }
 
 impl Default for Settings {
@@ -35,6 +76,25 @@ impl Default for Settings {
         Settings {
             filter_joins: true,
             goats_per_player: 1,
+            control_mode: chat_control::ControlMode::default(),
+            democracy_interval: 3.,
+            team_mode: team::TeamMode::default(),
+            game_mode: game_mode::GameMode::default(),
+            hazards: false,
+            max_goats: None,
+            auto_start_goats: None,
+            auto_start_secs: None,
+            join_selection: lobby::JoinSelection::default(),
+            sub_priority: false,
+            late_joins: false,
+            respawn: respawn::RespawnMode::default(),
+            respawn_secs: 5.,
+            respawn_round_secs: 180.,
+            spawn_layout: spawn::SpawnLayout::default(),
+            spawn_facing: spawn::SpawnFacing::default(),
+            spawn_margin: 30.,
+            spawn_spacing: 25.,
+            goat_stats: goat_stats::StatsMode::default(),
         }
     }
 }
@@ -43,7 +103,7 @@ impl Default for Settings {
 pub struct ChannelName(pub String);
 
 #[derive(Component)]
-struct NameText;
+struct NameInput;
 
 #[derive(Component)]
 struct FilterText;
@@ -51,20 +111,64 @@ struct FilterText;
 #[derive(Component)]
 struct GPPText;
 
+#[derive(Component)]
+struct ControlText;
+
+#[derive(Component)]
+struct TeamText;
+
+#[derive(Component)]
+struct ModeText;
+
+#[derive(Component)]
+struct HazardText;
+
+#[derive(Component)]
+struct SelectionText;
+
+#[derive(Component)]
+struct SubPriorityText;
+
+#[derive(Component)]
+struct LateJoinText;
+
+#[derive(Component)]
+struct RespawnText;
+
+#[derive(Component)]
+struct SpawnText;
+
+#[derive(Component)]
+struct StatsText;
+
 #[derive(Component, PartialEq, Eq)]
 #[require(Button)]
-enum ButtonAction {
+pub enum ButtonAction {
     Connect,
     Start,
     ToggleFilter,
     ChangeGPP(bool),
+    CycleControl,
+    CycleTeams,
+    CycleMode,
+    ToggleHazards,
+    CycleSelection,
+    ToggleSubPriority,
+    ToggleLateJoins,
+    CycleRespawn,
+    CycleSpawn,
+    CycleStats,
+    NextRound,
+    WatchRecording,
+    ExportStats,
+    SaveConfig,
 }
 
 #[derive(Component)]
 #[require(Node)]
 struct MenuRootNode;
 
-fn setup_main_menu(mut commands: Commands) {
+fn setup_main_menu(mut commands: Commands, channel_name: Res<ChannelName>) {
     commands.spawn((
         MenuRootNode,
         Node {
@@ -85,9 +189,27 @@ fn setup_main_menu(mut commands: Commands) {
                 TextFont::default().with_font_size(100.),
             ),
             (
-                Text::new("channel name: "),
-                TextFont::default().with_font_size(30.),
-                NameText,
+                Node {
+                    align_items: AlignItems::Center,
+                    column_gap: Val::Px(10.),
+                    ..Default::default()
+                },
+                children![
+                    (
+                        Text::new("channel name:"),
+                        TextFont::default().with_font_size(30.),
+                    ),
+                    (
+                        NameInput,
+                        text_input::AutoFocus,
+                        text_input::text_input(
+                            TextInput::new(&channel_name.0, TextRule::ChannelName)
+                                .with_placeholder("your twitch channel"),
+                            Val::Px(400.),
+                            30.
+                        ),
+                    )
+                ]
             ),
             button(ButtonAction::Connect, "CONNECT", Val::Px(150.)),
             Node {
@@ -100,33 +222,16 @@ fn setup_main_menu(mut commands: Commands) {
     commands.spawn((
         MenuRootNode,
         Node {
-            height: Val::Percent(10.),
+            height: Val::Percent(65.),
             flex_direction: FlexDirection::Column,
             justify_content: JustifyContent::SpaceBetween,
             ..Default::default()
         },
         children![
-            (
+            setting_button(
                 ButtonAction::ToggleFilter,
-                Node {
-                    width: Val::Px(400.),
-                    height: Val::Px(60.),
-                    align_items: AlignItems::Center,
-                    justify_content: JustifyContent::Center,
-                    border: UiRect::all(Val::Px(5.)),
-                    ..Default::default()
-                },
-                BorderColor(basic::BLACK.into()),
-                BorderRadius::all(Val::Px(8.)),
-                BackgroundColor(basic::GRAY.into()),
-                children![(
-                    Text::new("Only count 'play!' messages: yes".to_owned()),
-                    TextFont {
-                        font_size: 20.,
-                        ..Default::default()
-                    },
-                    FilterText,
-                )]
+                "Only count 'play!' messages: yes",
+                FilterText
             ),
             (
                 Node {
@@ -145,13 +250,66 @@ fn setup_main_menu(mut commands: Commands) {
                         GPPText,
                     )
                 ]
-            )
+            ),
+            setting_button(
+                ButtonAction::CycleControl,
+                "Chat controls goats: off",
+                ControlText
+            ),
+            setting_button(ButtonAction::CycleTeams, "Teams: off", TeamText),
+            setting_button(
+                ButtonAction::CycleMode,
+                "Mode: last goat standing",
+                ModeText
+            ),
+            setting_button(ButtonAction::ToggleHazards, "Hazards: no", HazardText),
+            setting_button(
+                ButtonAction::CycleSelection,
+                "Full lobby: queue",
+                SelectionText
+            ),
+            setting_button(
+                ButtonAction::ToggleSubPriority,
+                "Subs skip the queue: no",
+                SubPriorityText
+            ),
+            setting_button(
+                ButtonAction::ToggleLateJoins,
+                "Join mid round: no",
+                LateJoinText
+            ),
+            setting_button(ButtonAction::CycleRespawn, "Respawns: off", RespawnText),
+            setting_button(ButtonAction::CycleSpawn, "Spawns: scatter", SpawnText),
+            setting_button(
+                ButtonAction::CycleStats,
+                "Goat stats: same goat every time",
+                StatsText
+            ),
+            button(ButtonAction::SaveConfig, "Save settings", Val::Px(400.))
         ],
     ));
+
+    if recording::latest_recording().is_some() {
+        commands.spawn((
+            MenuRootNode,
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(10.),
+                right: Val::Px(10.),
+                ..Default::default()
+            },
+            children![button(
+                ButtonAction::WatchRecording,
+                "WATCH LAST ROUND",
+                Val::Px(320.)
+            )],
+        ));
+    }
 }
 
 fn update_menu(
     mut commands: Commands,
+    mut focus: ResMut<text_input::TextFocus>,
     buttons_query: Query<(&Children, &mut Node, &mut ButtonAction)>,
 ) {
     for (children, mut node, mut button_action) in buttons_query {
@@ -159,6 +317,7 @@ fn update_menu(
             continue;
         }
         *button_action = ButtonAction::Start;
+        focus.0 = None;
 
         commands
             .entity(children[0])
@@ -169,7 +328,7 @@ fn update_menu(
     }
 }
 
-fn button(action: ButtonAction, text: impl Into<String>, width: Val) -> impl Bundle {
+pub fn button(action: impl Component, text: impl Into<String>, width: Val) -> impl Bundle {
     (
         action,
         Node {
@@ -193,11 +352,44 @@ fn button(action: ButtonAction, text: impl Into<String>, width: Val) -> impl Bun
     )
 }
 
+// wide button for the settings in the corner, the text gets updated by its marker
+fn setting_button(
+    action: ButtonAction,
+    text: impl Into<String>,
+    text_marker: impl Component,
+) -> impl Bundle {
+    (
+        action,
+        Node {
+            width: Val::Px(400.),
+            height: Val::Px(60.),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            border: UiRect::all(Val::Px(5.)),
+            ..Default::default()
+        },
+        BorderColor(basic::BLACK.into()),
+        BorderRadius::all(Val::Px(8.)),
+        BackgroundColor(basic::GRAY.into()),
+        children![(
+            Text::new(text.into()),
+            TextFont {
+                font_size: 20.,
+                ..Default::default()
+            },
+            text_marker,
+        )],
+    )
+}
+
 fn button_system(
     mut connect_events: EventWriter<twitch::ConnectEvent>,
+    mut save_events: EventWriter<config::SaveConfig>,
     mut next_game_state: ResMut<NextState<GameState>>,
     mut settings: ResMut<Settings>,
     channel_name: Res<ChannelName>,
+    all_time: Res<viewer_stats::AllTimeStats>,
+    replay: Res<replay::Replay>,
     mut button_query: Query<
         (&Interaction, &ButtonAction, &mut BackgroundColor),
         (Changed<Interaction>, With<Button>),
@@ -208,8 +400,12 @@ fn button_system(
             Interaction::Pressed => {
                 match action {
                     ButtonAction::Connect => {
-                        connect_events.write(twitch::ConnectEvent(channel_name.0.clone()));
-                        next_game_state.set(GameState::Connected);
+                        connect(
+                            &channel_name.0,
+                            &mut connect_events,
+                            &mut save_events,
+                            &mut next_game_state,
+                        );
                     }
                     ButtonAction::Start => {
                         next_game_state.set(GameState::Spectating);
@@ -232,6 +428,55 @@ fn button_system(
                             settings.goats_per_player -= diff;
                         }
                     }
+                    ButtonAction::CycleControl => {
+                        settings.control_mode = settings.control_mode.next();
+                    }
+                    ButtonAction::CycleTeams => {
+                        settings.team_mode = settings.team_mode.next();
+                    }
+                    ButtonAction::CycleMode => {
+                        settings.game_mode = settings.game_mode.next();
+                    }
+                    ButtonAction::ToggleHazards => {
+                        settings.hazards = !settings.hazards;
+                    }
+                    ButtonAction::CycleSelection => {
+                        settings.join_selection = settings.join_selection.next();
+                    }
+                    ButtonAction::ToggleSubPriority => {
+                        settings.sub_priority = !settings.sub_priority;
+                    }
+                    ButtonAction::ToggleLateJoins => {
+                        settings.late_joins = !settings.late_joins;
+                    }
+                    ButtonAction::CycleRespawn => {
+                        settings.respawn = settings.respawn.next();
+                    }
+                    ButtonAction::CycleSpawn => {
+                        settings.spawn_layout = settings.spawn_layout.next();
+                    }
+                    ButtonAction::CycleStats => {
+                        settings.goat_stats = settings.goat_stats.next();
+                    }
+                    // the replay behind the end screen gets to finish first
+                    ButtonAction::NextRound => {
+                        if !replay.is_playing() {
+                            next_game_state.set(GameState::Connected);
+                        }
+                    }
+                    ButtonAction::WatchRecording => {
+                        next_game_state.set(GameState::Playback);
+                    }
+                    ButtonAction::ExportStats => {
+                        let path = std::path::Path::new(viewer_stats::EXPORT_FILE);
+                        match all_time.export_csv(path) {
+                            Ok(()) => info!("exported viewer stats to {}", path.display()),
+                            Err(err) => error!("couldnt export viewer stats: {err}"),
+                        }
+                    }
+                    ButtonAction::SaveConfig => {
+                        save_events.write(config::SaveConfig);
+                    }
                 };
             }
             Interaction::Hovered => {
@@ -244,21 +489,64 @@ fn button_system(
     }
 }
 
-fn update_name(mut name: ResMut<ChannelName>, input: Res<ButtonInput<KeyCode>>) {
-    for keycode in input.get_just_pressed() {
-        match util::keycode_to_string(keycode) {
-            Ok(s) => name.0 += s,
-            Err(_) => {
-                if *keycode == KeyCode::Backspace {
-                    name.0.pop();
-                }
-            }
-        };
+fn connect(
+    channel_name: &str,
+    connect_events: &mut EventWriter<twitch::ConnectEvent>,
+    save_events: &mut EventWriter<config::SaveConfig>,
+    next_game_state: &mut NextState<GameState>,
+) {
+    if TextRule::ChannelName.check(channel_name).is_err() {
+        return;
     }
+    connect_events.write(twitch::ConnectEvent(channel_name.to_owned()));
+    // so the channel name is still there next stream
+    save_events.write(config::SaveConfig);
+    next_game_state.set(GameState::Connected);
 }
 
-fn update_name_text(name: Res<ChannelName>, mut text_query: Single<&mut Text, With<NameText>>) {
-    text_query.0 = "channel name: ".to_owned() + &name.0.clone();
+// the name can also come from the config file after the menu is already there
+fn sync_name_input(
+    name: Res<ChannelName>,
+    mut input_query: Query<&mut TextInput, With<NameInput>>,
+) {
+    if !name.is_changed() {
+        return;
+    }
+    for mut input in &mut input_query {
+        if input.text() != name.0 {
+            input.set_text(&name.0);
+        }
+    }
+}
+
+fn update_name(
+    mut name: ResMut<ChannelName>,
+    input_query: Query<&TextInput, (With<NameInput>, Changed<TextInput>)>,
+) {
+    for input in &input_query {
+        if name.0 != input.text() {
+            name.0 = input.text().to_owned();
+        }
+    }
+}
+
+fn submit_name(
+    mut submit_events: EventReader<text_input::TextSubmitted>,
+    mut connect_events: EventWriter<twitch::ConnectEvent>,
+    mut save_events: EventWriter<config::SaveConfig>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    input_query: Query<(), With<NameInput>>,
+) {
+    for event in submit_events.read() {
+        if input_query.contains(event.input) {
+            connect(
+                &event.text,
+                &mut connect_events,
+                &mut save_events,
+                &mut next_game_state,
+            );
+        }
+    }
 }
 
 fn update_filter_text(
@@ -273,19 +561,72 @@ fn update_gpp_text(settings: Res<Settings>, mut text_query: Single<&mut Text, Wi
     text_query.0 = "Goats per player: ".to_owned() + &settings.goats_per_player.to_string();
 }
 
+fn update_control_text(
+    settings: Res<Settings>,
+    mut text_query: Single<&mut Text, With<ControlText>>,
+) {
+    text_query.0 = "Chat controls goats: ".to_owned() + settings.control_mode.label();
+}
+
+fn update_team_text(settings: Res<Settings>, mut text_query: Single<&mut Text, With<TeamText>>) {
+    text_query.0 = "Teams: ".to_owned() + settings.team_mode.label();
+}
+
+fn update_mode_text(settings: Res<Settings>, mut text_query: Single<&mut Text, With<ModeText>>) {
+    text_query.0 = "Mode: ".to_owned() + settings.game_mode.label();
+}
+
+fn update_hazard_text(
+    settings: Res<Settings>,
+    mut text_query: Single<&mut Text, With<HazardText>>,
+) {
+    text_query.0 = "Hazards: ".to_owned() + if settings.hazards { "yes" } else { "no" };
+}
+
+fn update_selection_text(
+    settings: Res<Settings>,
+    mut text_query: Single<&mut Text, With<SelectionText>>,
+) {
+    text_query.0 = "Full lobby: ".to_owned() + settings.join_selection.label();
+}
+
+fn update_sub_priority_text(
+    settings: Res<Settings>,
+    mut text_query: Single<&mut Text, With<SubPriorityText>>,
+) {
+    text_query.0 =
+        "Subs skip the queue: ".to_owned() + if settings.sub_priority { "yes" } else { "no" };
+}
+
+fn update_late_join_text(
+    settings: Res<Settings>,
+    mut text_query: Single<&mut Text, With<LateJoinText>>,
+) {
+    text_query.0 = "Join mid round: ".to_owned() + if settings.late_joins { "yes" } else { "no" };
+}
+
+fn update_respawn_text(
+    settings: Res<Settings>,
+    mut text_query: Single<&mut Text, With<RespawnText>>,
+) {
+    text_query.0 = "Respawns: ".to_owned() + settings.respawn.label();
+}
+
+fn update_spawn_text(settings: Res<Settings>, mut text_query: Single<&mut Text, With<SpawnText>>) {
+    text_query.0 = "Spawns: ".to_owned() + settings.spawn_layout.label();
+}
+
+fn update_stats_text(settings: Res<Settings>, mut text_query: Single<&mut Text, With<StatsText>>) {
+    text_query.0 = "Goat stats: ".to_owned() + settings.goat_stats.label();
+}
+
 fn despawn_main_menu(mut commands: Commands, menu_query: Query<Entity, With<MenuRootNode>>) {
     for menu in menu_query {
         commands.entity(menu).despawn();
     }
 }
 
-fn setup_end_menu(mut commands: Commands, alive_players: Res<player::Players>) {
-    let name_opt = alive_players.0.iter().last();
-    let name = match name_opt {
-        Some(n) => n.clone(),
-        None => "SOMEONE".to_owned(),
-    };
-
+fn setup_end_menu(mut commands: Commands, round_result: Res<game_mode::RoundResult>) {
     commands.spawn((
         MenuRootNode,
         Node {
@@ -302,9 +643,15 @@ fn setup_end_menu(mut commands: Commands, alive_players: Res<player::Players>) {
                 ..Default::default()
             },
             (
-                Text::new(name + " WON"),
+                Text::new(round_result.title.clone()),
                 TextFont::default().with_font_size(100.),
             ),
+            (
+                Text::new(round_result.lines.join("\n")),
+                TextFont::default().with_font_size(30.),
+                TextLayout::new_with_justify(JustifyText::Center),
+            ),
+            button(ButtonAction::NextRound, "NEXT ROUND", Val::Px(250.)),
             Node {
                 height: Val::Percent(0.),
                 ..Default::default()
@@ -0,0 +1,138 @@
+use crate::{player, GameState};
+use bevy::prelude::*;
+
+// seconds between two keyframes
+const SECS_PER_KEY: f32 = 6.;
+
+pub struct MenuCameraPlugin;
+
+impl Plugin for MenuCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            fly_over.run_if(in_state(GameState::Start).or(in_state(GameState::Connected))),
+        )
+        .add_systems(OnEnter(GameState::Spectating), reset_camera);
+    }
+}
+
+/// one point on the menu flyover path
+pub struct FlyoverKey {
+    pub pos: Vec3,
+    pub look_at: Vec3,
+}
+
+/// the path the camera loops around while the menu is open, a map can bring its own
+pub const ARENA_FLYOVER: &[FlyoverKey] = &[
+    FlyoverKey {
+        pos: vec3(0., 60., -340.),
+        look_at: vec3(0., 0., 0.),
+    },
+    FlyoverKey {
+        pos: vec3(250., 40., -250.),
+        look_at: vec3(0., 0., -50.),
+    },
+    FlyoverKey {
+        pos: vec3(340., 80., 0.),
+        look_at: vec3(0., 0., 0.),
+    },
+    FlyoverKey {
+        pos: vec3(150., 20., 200.),
+        look_at: vec3(-50., 0., 0.),
+    },
+    FlyoverKey {
+        pos: vec3(-200., 120., 250.),
+        look_at: vec3(0., 0., 0.),
+    },
+    FlyoverKey {
+        pos: vec3(-320., 30., -100.),
+        look_at: vec3(50., 0., 0.),
+    },
+];
+
+// uniform catmull-rom between b and c
+fn catmull_rom(a: Vec3, b: Vec3, c: Vec3, d: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * ((2. * b)
+        + (c - a) * t
+        + (2. * a - 5. * b + 4. * c - d) * t2
+        + (3. * b - a - 3. * c + d) * t3)
+}
+
+fn sample_path(keys: &[FlyoverKey], time: f32) -> (Vec3, Vec3) {
+    let n = keys.len();
+    let progress = (time / SECS_PER_KEY) % n as f32;
+    let i = progress as usize;
+    let t = progress.fract();
+    let key = |offset: usize| &keys[(i + n + offset - 1) % n];
+
+    (
+        catmull_rom(key(0).pos, key(1).pos, key(2).pos, key(3).pos, t),
+        catmull_rom(
+            key(0).look_at,
+            key(1).look_at,
+            key(2).look_at,
+            key(3).look_at,
+            t,
+        ),
+    )
+}
+
+fn fly_over(
+    time: Res<Time>,
+    mut camera_query: Single<&mut Transform, (With<Camera>, Without<player::Player>)>,
+    player_query: Query<&Transform, With<player::Player>>,
+) {
+    let (pos, mut look_at) = sample_path(ARENA_FLYOVER, time.elapsed_secs());
+
+    // lean towards the goats that already joined so chat can see themselves
+    if !player_query.is_empty() {
+        let goats_center = player_query
+            .iter()
+            .map(|trans| trans.translation)
+            .sum::<Vec3>()
+            / player_query.iter().len() as f32;
+        look_at = look_at.lerp(goats_center, 0.5);
+    }
+
+    **camera_query = Transform::from_translation(pos).looking_at(look_at, Vec3::Y);
+}
+
+fn reset_camera(mut camera_query: Single<&mut Transform, With<Camera>>) {
+    **camera_query = Transform::from_xyz(0., 10., -300.).looking_to(Vec3::Z, Vec3::Y);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_goes_through_every_key() {
+        for (i, key) in ARENA_FLYOVER.iter().enumerate() {
+            let (pos, look_at) = sample_path(ARENA_FLYOVER, i as f32 * SECS_PER_KEY);
+            assert!(pos.abs_diff_eq(key.pos, 0.01));
+            assert!(look_at.abs_diff_eq(key.look_at, 0.01));
+        }
+    }
+
+    #[test]
+    fn path_loops_back_to_the_start() {
+        let lap = ARENA_FLYOVER.len() as f32 * SECS_PER_KEY;
+        let (start, _) = sample_path(ARENA_FLYOVER, 0.);
+        let (end, _) = sample_path(ARENA_FLYOVER, lap - 0.001);
+        let (next_lap, _) = sample_path(ARENA_FLYOVER, lap + 1.);
+        assert!(end.distance(start) < 1.);
+        assert!(next_lap.abs_diff_eq(sample_path(ARENA_FLYOVER, 1.).0, 0.01));
+    }
+
+    #[test]
+    fn path_has_no_jumps() {
+        let mut last = sample_path(ARENA_FLYOVER, 0.).0;
+        for step in 1..=600 {
+            let (pos, _) = sample_path(ARENA_FLYOVER, step as f32 * 0.1);
+            assert!(pos.distance(last) < 20.);
+            last = pos;
+        }
+    }
+}
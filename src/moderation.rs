@@ -0,0 +1,272 @@
+use crate::{feed, game_mode, lobby, pause, player, twitch, util, GameState};
+use bevy::prelude::*;
+use regex::Regex;
+use std::io::Write;
+use std::path::Path;
+
+pub const AUDIT_FILE: &str = "mod_log.txt";
+/// who did it when the streamer presses a button
+pub const STREAMER: &str = "streamer";
+/// the parts of names that get a goat renamed, checked after undoing leetspeak
+pub const DEFAULT_BLOCKED_NAMES: &[&str] = &[
+    "fuck", "shit", "cunt", "bitch", "nigg", "faggot", "retard", "nazi", "hitler",
+];
+
+pub struct ModerationPlugin;
+
+impl Plugin for ModerationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(NameFilter::default())
+            .add_event::<ModAction>()
+            .add_systems(Update, (read_mod_commands, apply_mod_actions).chain());
+    }
+}
+
+/// names matching any of these are replaced wherever theyre shown, everything else keeps using the login
+#[derive(Resource, Default)]
+pub struct NameFilter(Vec<Regex>);
+
+impl NameFilter {
+    pub fn new(patterns: &[String]) -> Self {
+        let mut regexes = vec![];
+        for pattern in patterns {
+            match Regex::new(&format!("(?i){pattern}")) {
+                Ok(regex) => regexes.push(regex),
+                Err(err) => error!("blocked name {pattern} isnt a valid regex: {err}"),
+            }
+        }
+        NameFilter(regexes)
+    }
+
+    pub fn is_allowed(&self, name: &str) -> bool {
+        let plain = name
+            .chars()
+            .filter(|c| *c != '_')
+            .map(|c| match c {
+                '0' => 'o',
+                '1' => 'i',
+                '3' => 'e',
+                '4' => 'a',
+                '5' => 's',
+                '7' => 't',
+                c => c,
+            })
+            .collect::<String>();
+        !self
+            .0
+            .iter()
+            .any(|regex| regex.is_match(name) || regex.is_match(&plain))
+    }
+
+    /// the name itself or a made up one to show instead, the same viewer always gets the same one
+    pub fn display(&self, name: &str) -> String {
+        if self.is_allowed(name) {
+            return name.to_owned();
+        }
+        format!("goat{:04}", util::fnv1a(name) % 10000)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ModCommand {
+    Kick(String),
+    Ban(String),
+    Unban(String),
+    EndRound,
+    Restart,
+}
+
+impl ModCommand {
+    // !kick someone, !ban @someone, !endround
+    fn parse(text: &str) -> Option<Self> {
+        let mut words = text.split_whitespace();
+        let command = words.next()?;
+        let target = words
+            .next()
+            .map(|name| name.trim_start_matches('@').to_lowercase());
+        match (command, target) {
+            ("!kick", Some(viewer)) => Some(ModCommand::Kick(viewer)),
+            ("!ban", Some(viewer)) => Some(ModCommand::Ban(viewer)),
+            ("!unban", Some(viewer)) => Some(ModCommand::Unban(viewer)),
+            ("!endround", _) => Some(ModCommand::EndRound),
+            ("!restart", _) => Some(ModCommand::Restart),
+            _ => None,
+        }
+    }
+
+    // the same command with the name of the viewer as its shown
+    fn display(&self, name_filter: &NameFilter) -> Self {
+        match self {
+            ModCommand::Kick(viewer) => ModCommand::Kick(name_filter.display(viewer)),
+            ModCommand::Ban(viewer) => ModCommand::Ban(name_filter.display(viewer)),
+            ModCommand::Unban(viewer) => ModCommand::Unban(name_filter.display(viewer)),
+            command => command.clone(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            ModCommand::Kick(viewer) => format!("kicked {viewer}"),
+            ModCommand::Ban(viewer) => format!("banned {viewer}"),
+            ModCommand::Unban(viewer) => format!("unbanned {viewer}"),
+            ModCommand::EndRound => "ended the round".to_owned(),
+            ModCommand::Restart => "restarted the round".to_owned(),
+        }
+    }
+}
+
+/// something a mod or the streamer did to a viewer or the round, everything goes in the audit log
+#[derive(Event, Clone, Debug)]
+pub struct ModAction {
+    pub by: String,
+    pub command: ModCommand,
+}
+
+fn read_mod_commands(
+    mut chat_events: EventReader<twitch::ChatMessage>,
+    mut mod_events: EventWriter<ModAction>,
+) {
+    for event in chat_events.read() {
+        if !event.0.is_mod() {
+            continue;
+        }
+        let Some(command) = ModCommand::parse(&event.0.text) else {
+            continue;
+        };
+        mod_events.write(ModAction {
+            by: event.0.sender.clone(),
+            command,
+        });
+    }
+}
+
+fn apply_mod_actions(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    name_filter: Res<NameFilter>,
+    mut players: ResMut<player::Players>,
+    mut ban_list: ResMut<lobby::BanList>,
+    mut queue: ResMut<lobby::JoinQueue>,
+    mut force_end: ResMut<game_mode::ForceEnd>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut mod_events: EventReader<ModAction>,
+    mut feed_events: EventWriter<feed::FeedEvent>,
+    player_query: Query<(Entity, &player::Player, Has<player::Bot>)>,
+) {
+    for action in mod_events.read() {
+        let in_round = *state.get() == GameState::Spectating;
+        match &action.command {
+            ModCommand::Kick(viewer) => {
+                lobby::kick(&mut commands, &mut players, &player_query, viewer, false);
+            }
+            ModCommand::Ban(viewer) => {
+                lobby::kick(&mut commands, &mut players, &player_query, viewer, false);
+                queue
+                    .0
+                    .retain(|queued| !queued.sender.eq_ignore_ascii_case(viewer));
+                ban_list.insert(viewer);
+                if let Err(err) = ban_list.save(Path::new(lobby::BAN_FILE)) {
+                    error!("couldnt save the ban list: {err}");
+                }
+            }
+            ModCommand::Unban(viewer) => {
+                ban_list.remove(viewer);
+                if let Err(err) = ban_list.save(Path::new(lobby::BAN_FILE)) {
+                    error!("couldnt save the ban list: {err}");
+                }
+            }
+            // ending or restarting only makes sense while a round is going
+            ModCommand::EndRound | ModCommand::Restart if !in_round => continue,
+            ModCommand::EndRound => {
+                force_end.0 = true;
+                virtual_time.unpause();
+            }
+            ModCommand::Restart => {
+                next_game_state.set(GameState::Restarting);
+            }
+        }
+
+        // the log gets the real logins, the stream only the names it would show anyway
+        let text = format!("{} {}", action.by, action.command.describe());
+        info!("{text}");
+        if let Err(err) = append_audit_log(Path::new(AUDIT_FILE), &text) {
+            error!("couldnt write to the mod log: {err}");
+        }
+        feed_events.write(feed::FeedEvent::Moderation {
+            text: format!(
+                "{} {}",
+                name_filter.display(&action.by),
+                action.command.display(&name_filter).describe()
+            ),
+        });
+    }
+}
+
+fn append_audit_log(path: &Path, text: &str) -> std::io::Result<()> {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs());
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{secs} {text}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_parse() {
+        assert_eq!(
+            ModCommand::parse("!kick @SomeOne"),
+            Some(ModCommand::Kick("someone".to_owned()))
+        );
+        assert_eq!(
+            ModCommand::parse("!ban someone for spamming"),
+            Some(ModCommand::Ban("someone".to_owned()))
+        );
+        assert_eq!(
+            ModCommand::parse("!unban someone"),
+            Some(ModCommand::Unban("someone".to_owned()))
+        );
+        // bans are kept by login and logins are lowercase
+        assert_eq!(
+            ModCommand::parse("!ban @MixedCaseViewer"),
+            Some(ModCommand::Ban("mixedcaseviewer".to_owned()))
+        );
+        assert_eq!(ModCommand::parse("!endround"), Some(ModCommand::EndRound));
+        assert_eq!(ModCommand::parse("!restart now"), Some(ModCommand::Restart));
+        assert_eq!(ModCommand::parse("!kick"), None);
+        assert_eq!(ModCommand::parse("kick someone"), None);
+        assert_eq!(ModCommand::parse(""), None);
+    }
+
+    #[test]
+    fn blocked_names_are_caught() {
+        let filter = NameFilter::new(&["badword".to_owned(), "^evil".to_owned()]);
+        assert!(filter.is_allowed("nicegoat"));
+        assert!(!filter.is_allowed("BadWord123"));
+        assert!(!filter.is_allowed("b4dw0rd"));
+        assert!(!filter.is_allowed("bad_word"));
+        assert!(!filter.is_allowed("evilgoat"));
+        assert!(filter.is_allowed("notevil"));
+    }
+
+    #[test]
+    fn invalid_patterns_are_skipped() {
+        let filter = NameFilter::new(&["(".to_owned(), "bad".to_owned()]);
+        assert!(filter.is_allowed("goat"));
+        assert!(!filter.is_allowed("bad"));
+    }
+
+    #[test]
+    fn blocked_names_get_the_same_alias() {
+        let filter = NameFilter::new(&["bad".to_owned()]);
+        assert_eq!(filter.display("goat"), "goat");
+        assert_eq!(filter.display("badgoat"), filter.display("badgoat"));
+        assert!(filter.display("badgoat").starts_with("goat"));
+    }
+}
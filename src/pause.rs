@@ -0,0 +1,344 @@
+use crate::{
+    config, feed, follow_camera, game_mode, menu, player, replay, scoreboard, spawn,
+    spectator_camera::{self, FreeFly},
+    AssetHandles, GameState,
+};
+use bevy::audio::Volume;
+use bevy::color::palettes::basic;
+use bevy::prelude::*;
+
+const VOLUME_STEP: f32 = 0.1;
+const CAMERA_SPEED_STEP: f32 = 1.25;
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Paused::default())
+            .add_systems(
+                Update,
+                (
+                    // escape cancels typing a name to follow first
+                    toggle_pause
+                        .before(follow_camera::type_name)
+                        .run_if(replay::not_replaying.and(follow_camera::not_searching)),
+                    (pause_button_system, update_pause_labels)
+                        .chain()
+                        .run_if(resource_equals(Paused(true))),
+                )
+                    .run_if(in_state(GameState::Spectating)),
+            )
+            .add_systems(OnExit(GameState::Spectating), unpause)
+            .add_systems(OnEnter(GameState::Restarting), finish_restart);
+    }
+}
+
+/// the round is frozen and the overlay is up
+#[derive(Resource, Default, PartialEq, Eq)]
+pub struct Paused(pub bool);
+
+pub fn not_paused(paused: Res<Paused>) -> bool {
+    !paused.0
+}
+
+#[derive(Component)]
+struct PauseOverlay;
+
+#[derive(Component, PartialEq, Eq)]
+#[require(Button)]
+enum PauseButton {
+    Resume,
+    ChangeVolume(bool),
+    ChangeCameraSpeed(bool),
+    ToggleScoreboard,
+    ToggleFeed,
+    SaveSettings,
+    EndRound,
+    Restart,
+    Quit,
+}
+
+#[derive(Component, Clone, Copy)]
+enum PauseLabel {
+    Volume,
+    CameraSpeed,
+    Scoreboard,
+    Feed,
+}
+
+fn toggle_pause(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    mut paused: ResMut<Paused>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut window: Single<&mut Window>,
+    overlay_query: Query<Entity, With<PauseOverlay>>,
+) {
+    if !input.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    if paused.0 {
+        resume(
+            &mut commands,
+            &mut paused,
+            &mut virtual_time,
+            &mut window,
+            &overlay_query,
+        );
+        return;
+    }
+
+    paused.0 = true;
+    virtual_time.pause();
+    window.cursor_options = crate::cursor_options(false);
+    spawn_overlay(&mut commands);
+}
+
+fn resume(
+    commands: &mut Commands,
+    paused: &mut ResMut<Paused>,
+    virtual_time: &mut ResMut<Time<Virtual>>,
+    window: &mut Single<&mut Window>,
+    overlay_query: &Query<Entity, With<PauseOverlay>>,
+) {
+    paused.0 = false;
+    virtual_time.unpause();
+    window.cursor_options = crate::cursor_options(true);
+    for overlay in overlay_query {
+        commands.entity(overlay).despawn();
+    }
+}
+
+fn unpause(
+    mut commands: Commands,
+    mut paused: ResMut<Paused>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    overlay_query: Query<Entity, With<PauseOverlay>>,
+) {
+    if !paused.0 {
+        return;
+    }
+    paused.0 = false;
+    virtual_time.unpause();
+    for overlay in &overlay_query {
+        commands.entity(overlay).despawn();
+    }
+}
+
+fn label(text: &str, marker: PauseLabel) -> impl Bundle {
+    (
+        marker,
+        Text::new(text),
+        TextFont::default().with_font_size(26.),
+        Node {
+            width: Val::Px(300.),
+            ..Default::default()
+        },
+    )
+}
+
+fn setting_row(
+    text: &str,
+    marker: PauseLabel,
+    less: PauseButton,
+    more: PauseButton,
+) -> impl Bundle {
+    (
+        Node {
+            align_items: AlignItems::Center,
+            column_gap: Val::Px(10.),
+            ..Default::default()
+        },
+        children![
+            label(text, marker),
+            menu::button(less, "-", Val::Px(60.)),
+            menu::button(more, "+", Val::Px(60.)),
+        ],
+    )
+}
+
+fn spawn_overlay(commands: &mut Commands) {
+    commands.spawn((
+        PauseOverlay,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(12.),
+            ..Default::default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.6)),
+        // above the hud
+        GlobalZIndex(10),
+        children![
+            (Text::new("PAUSED"), TextFont::default().with_font_size(80.)),
+            setting_row(
+                "Volume",
+                PauseLabel::Volume,
+                PauseButton::ChangeVolume(false),
+                PauseButton::ChangeVolume(true)
+            ),
+            setting_row(
+                "Camera speed",
+                PauseLabel::CameraSpeed,
+                PauseButton::ChangeCameraSpeed(false),
+                PauseButton::ChangeCameraSpeed(true)
+            ),
+            (
+                Node {
+                    column_gap: Val::Px(10.),
+                    ..Default::default()
+                },
+                children![
+                    (
+                        menu::button(PauseButton::ToggleScoreboard, "", Val::Px(250.)),
+                        PauseLabel::Scoreboard
+                    ),
+                    (
+                        menu::button(PauseButton::ToggleFeed, "", Val::Px(250.)),
+                        PauseLabel::Feed
+                    ),
+                ]
+            ),
+            menu::button(PauseButton::Resume, "RESUME", Val::Px(400.)),
+            menu::button(PauseButton::SaveSettings, "Save settings", Val::Px(400.)),
+            menu::button(PauseButton::EndRound, "END ROUND", Val::Px(400.)),
+            menu::button(PauseButton::Restart, "RESTART ROUND", Val::Px(400.)),
+            menu::button(PauseButton::Quit, "QUIT", Val::Px(400.)),
+        ],
+    ));
+}
+
+fn pause_button_system(
+    mut commands: Commands,
+    mut paused: ResMut<Paused>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut window: Single<&mut Window>,
+    overlay_query: Query<Entity, With<PauseOverlay>>,
+    mut global_volume: ResMut<GlobalVolume>,
+    mut free_fly: ResMut<FreeFly>,
+    mut scoreboard_visible: ResMut<scoreboard::ScoreboardVisible>,
+    mut feed_style: ResMut<feed::FeedStyle>,
+    mut save_events: EventWriter<config::SaveConfig>,
+    mut force_end: ResMut<game_mode::ForceEnd>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut exit_events: EventWriter<AppExit>,
+    mut button_query: Query<
+        (&Interaction, &PauseButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+) {
+    for (interaction, action, mut color) in &mut button_query {
+        match *interaction {
+            Interaction::Pressed => match action {
+                PauseButton::Resume => resume(
+                    &mut commands,
+                    &mut paused,
+                    &mut virtual_time,
+                    &mut window,
+                    &overlay_query,
+                ),
+                PauseButton::ChangeVolume(up) => {
+                    let step = if *up { VOLUME_STEP } else { -VOLUME_STEP };
+                    let volume = (global_volume.volume.to_linear() + step).clamp(0., 1.);
+                    global_volume.volume = Volume::Linear(volume);
+                }
+                PauseButton::ChangeCameraSpeed(up) => {
+                    let step = if *up {
+                        CAMERA_SPEED_STEP
+                    } else {
+                        1. / CAMERA_SPEED_STEP
+                    };
+                    free_fly.speed = (free_fly.speed * step)
+                        .clamp(spectator_camera::MIN_SPEED, spectator_camera::MAX_SPEED);
+                }
+                PauseButton::ToggleScoreboard => {
+                    scoreboard_visible.0 = !scoreboard_visible.0;
+                }
+                PauseButton::ToggleFeed => {
+                    feed_style.visible = !feed_style.visible;
+                }
+                PauseButton::SaveSettings => {
+                    save_events.write(config::SaveConfig);
+                }
+                // the overlay goes away with the round
+                PauseButton::EndRound => {
+                    force_end.0 = true;
+                    virtual_time.unpause();
+                }
+                PauseButton::Restart => {
+                    next_game_state.set(GameState::Restarting);
+                }
+                PauseButton::Quit => {
+                    exit_events.write(AppExit::Success);
+                }
+            },
+            Interaction::Hovered => {
+                *color = basic::BLACK.into();
+            }
+            Interaction::None => {
+                *color = basic::GRAY.into();
+            }
+        }
+    }
+}
+
+fn update_pause_labels(
+    global_volume: Res<GlobalVolume>,
+    free_fly: Res<FreeFly>,
+    scoreboard_visible: Res<scoreboard::ScoreboardVisible>,
+    feed_style: Res<feed::FeedStyle>,
+    label_query: Query<(Entity, &PauseLabel)>,
+    children_query: Query<&Children>,
+    mut text_query: Query<&mut Text>,
+) {
+    let shown = |visible: bool| if visible { "shown" } else { "hidden" };
+    for (entity, label) in &label_query {
+        let text = match label {
+            PauseLabel::Volume => {
+                format!("Volume: {:.0}%", global_volume.volume.to_linear() * 100.)
+            }
+            PauseLabel::CameraSpeed => format!("Camera speed: {:.0}", free_fly.speed),
+            PauseLabel::Scoreboard => format!("Scoreboard: {}", shown(scoreboard_visible.0)),
+            PauseLabel::Feed => format!("Feed: {}", shown(feed_style.visible)),
+        };
+        // the toggles keep their text on the button child
+        let target = children_query
+            .get(entity)
+            .ok()
+            .and_then(|children| children.first().copied())
+            .unwrap_or(entity);
+        if let Ok(mut label_text) = text_query.get_mut(target) {
+            if label_text.0 != text {
+                label_text.0 = text;
+            }
+        }
+    }
+}
+
+// clears out the old round and puts every goat that started it back on the field
+fn finish_restart(
+    mut commands: Commands,
+    asset_handles: Res<AssetHandles>,
+    roster: Res<player::Roster>,
+    planner: spawn::SpawnPlanner,
+    mut players: ResMut<player::Players>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    round_query: Query<Entity, Or<(With<player::Player>, With<game_mode::RoundProp>)>>,
+) {
+    for entity in &round_query {
+        commands.entity(entity).despawn();
+    }
+    players.0.clear();
+    player::respawn_roster(
+        &mut commands,
+        &asset_handles,
+        &planner,
+        &roster,
+        &mut players,
+    );
+    next_game_state.set(GameState::Spectating);
+}
@@ -0,0 +1,383 @@
+use crate::{
+    moderation, player,
+    recording::{self, MatchEvent, MatchRecording},
+    spectator_camera::CameraMode,
+    team::Team,
+    util, AssetHandles, GameState,
+};
+use bevy::color::palettes::basic;
+use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+const SCRUB_SECS: f32 = 5.;
+const MIN_SPEED: f32 = 0.125;
+const MAX_SPEED: f32 = 8.;
+const CHAT_LINES: usize = 8;
+// recorded bullets only exist to be seen, they vanish after this long
+const BULLET_SECS: f32 = 0.5;
+
+pub struct PlaybackPlugin;
+
+impl Plugin for PlaybackPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Playback::default())
+            .add_systems(
+                OnEnter(GameState::Playback),
+                (load_recording, setup_playback_hud).chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    playback_controls,
+                    advance_playback,
+                    move_bullets,
+                    update_playback_hud,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playback)),
+            )
+            .add_systems(OnExit(GameState::Playback), cleanup_playback);
+    }
+}
+
+#[derive(Resource)]
+struct Playback {
+    recording: MatchRecording,
+    names: HashMap<u32, (String, Option<Team>)>,
+    // seconds since the first tick
+    time: f32,
+    speed: f32,
+    paused: bool,
+    // the last tick whose events were played
+    played_tick: usize,
+    ghosts: HashMap<u32, Entity>,
+    chat: VecDeque<String>,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Playback {
+            recording: MatchRecording::default(),
+            names: HashMap::new(),
+            time: 0.,
+            speed: 1.,
+            paused: false,
+            played_tick: 0,
+            ghosts: HashMap::new(),
+            chat: VecDeque::new(),
+        }
+    }
+}
+
+impl Playback {
+    // the tick at or right before a playback time
+    fn tick_at(&self, time: f32) -> usize {
+        let Some(first) = self.recording.ticks.first() else {
+            return 0;
+        };
+        self.recording
+            .ticks
+            .partition_point(|tick| tick.time <= first.time + time)
+            .saturating_sub(1)
+    }
+
+    fn jump_to(&mut self, time: f32) {
+        self.time = time.clamp(0., self.recording.duration());
+        self.played_tick = self.tick_at(self.time);
+    }
+}
+
+// everything despawned when leaving playback
+#[derive(Component)]
+struct PlaybackEntity;
+
+#[derive(Component)]
+struct PlaybackBullet {
+    velocity: Vec3,
+    age: f32,
+}
+
+#[derive(Component)]
+struct TimelineText;
+
+#[derive(Component)]
+struct TimelineBar;
+
+#[derive(Component)]
+struct ChatLog;
+
+fn load_recording(
+    mut playback: ResMut<Playback>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut camera_mode: ResMut<CameraMode>,
+    mut camera_query: Single<&mut Transform, With<Camera>>,
+) {
+    let loaded = recording::latest_recording()
+        .ok_or_else(|| "theres no recording yet".to_owned())
+        .and_then(|path| MatchRecording::load(&path).map_err(|err| err.to_string()));
+    let recording = match loaded {
+        Ok(recording) if recording.ticks.len() >= 2 => recording,
+        Ok(_) => {
+            error!("the recording is empty");
+            next_game_state.set(GameState::Start);
+            return;
+        }
+        Err(err) => {
+            error!("couldnt load the recording: {err}");
+            next_game_state.set(GameState::Start);
+            return;
+        }
+    };
+
+    let mut names = HashMap::new();
+    for event in recording.ticks.iter().flat_map(|tick| &tick.events) {
+        if let MatchEvent::Spawn { id, name, team } = event {
+            names.insert(*id, (name.clone(), *team));
+        }
+    }
+
+    *playback = Playback {
+        recording,
+        names,
+        ..Default::default()
+    };
+    *camera_mode = CameraMode::Manual;
+    **camera_query = Transform::from_xyz(0., 150., -250.).looking_at(Vec3::ZERO, Vec3::Y);
+}
+
+fn playback_controls(
+    input: Res<ButtonInput<KeyCode>>,
+    mut playback: ResMut<Playback>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if input.just_pressed(KeyCode::Escape) {
+        next_game_state.set(GameState::Start);
+    }
+    if input.just_pressed(KeyCode::Space) {
+        playback.paused = !playback.paused;
+    }
+    if input.just_pressed(KeyCode::ArrowUp) {
+        playback.speed = (playback.speed * 2.).min(MAX_SPEED);
+    }
+    if input.just_pressed(KeyCode::ArrowDown) {
+        playback.speed = (playback.speed / 2.).max(MIN_SPEED);
+    }
+
+    let time = playback.time;
+    if input.just_pressed(KeyCode::ArrowLeft) {
+        playback.jump_to(time - SCRUB_SECS);
+    }
+    if input.just_pressed(KeyCode::ArrowRight) {
+        playback.jump_to(time + SCRUB_SECS);
+    }
+    if input.just_pressed(KeyCode::Home) {
+        playback.jump_to(0.);
+    }
+}
+
+fn advance_playback(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_handles: Res<AssetHandles>,
+    name_filter: Res<moderation::NameFilter>,
+    mut playback: ResMut<Playback>,
+    mut ghost_query: Query<&mut Transform, Without<Camera>>,
+) {
+    let playback = &mut *playback;
+    if playback.recording.ticks.len() < 2 {
+        return;
+    }
+    if !playback.paused {
+        playback.time =
+            (playback.time + time.delta_secs() * playback.speed).min(playback.recording.duration());
+    }
+
+    let current = playback.tick_at(playback.time);
+    let ticks = &playback.recording.ticks;
+
+    // play what happened in the ticks passed since the last frame, jumps skip this
+    if current > playback.played_tick {
+        for tick in &ticks[playback.played_tick + 1..=current] {
+            for event in &tick.events {
+                match event {
+                    MatchEvent::Bullet { pos, velocity } => {
+                        commands.spawn((
+                            PlaybackEntity,
+                            PlaybackBullet {
+                                velocity: *velocity,
+                                age: 0.,
+                            },
+                            Transform::from_translation(*pos),
+                            Mesh3d(asset_handles.the_sphere.clone().unwrap()),
+                            MeshMaterial3d(asset_handles.bullet_material.clone().unwrap()),
+                        ));
+                    }
+                    MatchEvent::Explosion { pos } => {
+                        commands.spawn(util::explosion(&asset_handles, *pos));
+                    }
+                    MatchEvent::Chat { sender, text } => {
+                        playback
+                            .chat
+                            .push_back(name_filter.display(sender) + ": " + text);
+                        if playback.chat.len() > CHAT_LINES {
+                            playback.chat.pop_front();
+                        }
+                    }
+                    MatchEvent::Spawn { .. } | MatchEvent::Death { .. } => {}
+                }
+            }
+        }
+    }
+    playback.played_tick = current;
+
+    let a = &ticks[current];
+    let b = &ticks[(current + 1).min(ticks.len() - 1)];
+    let now = ticks[0].time + playback.time;
+    let t = if b.time > a.time {
+        ((now - a.time) / (b.time - a.time)).clamp(0., 1.)
+    } else {
+        0.
+    };
+    let in_b = b
+        .goats
+        .iter()
+        .map(|goat| (goat.id, goat))
+        .collect::<HashMap<_, _>>();
+
+    // goats that arent in this tick anymore are dead or not spawned yet
+    playback.ghosts.retain(|id, ghost| {
+        let alive = a.goats.iter().any(|goat| goat.id == *id);
+        if !alive {
+            commands.entity(*ghost).despawn();
+        }
+        alive
+    });
+
+    for goat in &a.goats {
+        // ghosts have no collider so the whole thing is scaled, nameplate too
+        let trans = match in_b.get(&goat.id) {
+            Some(next) => Transform::from_translation(goat.translation.lerp(next.translation, t))
+                .with_rotation(goat.rotation.slerp(next.rotation, t)),
+            None => Transform::from_translation(goat.translation).with_rotation(goat.rotation),
+        }
+        .with_scale(Vec3::splat(goat.scale));
+
+        match playback.ghosts.get(&goat.id) {
+            Some(ghost) => {
+                if let Ok(mut ghost_trans) = ghost_query.get_mut(*ghost) {
+                    *ghost_trans = trans;
+                }
+            }
+            None => {
+                let (name, team) = playback.names.get(&goat.id).cloned().unwrap_or_default();
+                let ghost = commands
+                    .spawn((
+                        PlaybackEntity,
+                        player::ghost_goat(&asset_handles, name_filter.display(&name), team, trans),
+                    ))
+                    .id();
+                playback.ghosts.insert(goat.id, ghost);
+            }
+        }
+    }
+}
+
+fn move_bullets(
+    mut commands: Commands,
+    time: Res<Time>,
+    playback: Res<Playback>,
+    mut bullet_query: Query<(Entity, &mut Transform, &mut PlaybackBullet)>,
+) {
+    if playback.paused {
+        return;
+    }
+    let dt = time.delta_secs() * playback.speed;
+    for (entity, mut trans, mut bullet) in &mut bullet_query {
+        trans.translation += bullet.velocity * dt;
+        bullet.age += dt;
+        if bullet.age > BULLET_SECS {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn setup_playback_hud(mut commands: Commands) {
+    commands.spawn((
+        PlaybackEntity,
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(20.),
+            width: Val::Percent(100.),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(8.),
+            ..Default::default()
+        },
+        children![
+            (
+                TimelineText,
+                Text::new(""),
+                TextFont::default().with_font_size(25.),
+            ),
+            (
+                Node {
+                    width: Val::Percent(60.),
+                    height: Val::Px(10.),
+                    ..Default::default()
+                },
+                BackgroundColor(basic::GRAY.into()),
+                children![(
+                    TimelineBar,
+                    Node {
+                        width: Val::Percent(0.),
+                        height: Val::Percent(100.),
+                        ..Default::default()
+                    },
+                    BackgroundColor(basic::RED.into()),
+                )],
+            ),
+        ],
+    ));
+
+    commands.spawn((
+        PlaybackEntity,
+        ChatLog,
+        Text::new(""),
+        TextFont::default().with_font_size(20.),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.),
+            right: Val::Px(10.),
+            ..Default::default()
+        },
+    ));
+}
+
+fn update_playback_hud(
+    playback: Res<Playback>,
+    mut text_query: Single<&mut Text, (With<TimelineText>, Without<ChatLog>)>,
+    mut bar_query: Single<&mut Node, With<TimelineBar>>,
+    mut chat_query: Single<&mut Text, With<ChatLog>>,
+) {
+    let duration = playback.recording.duration();
+    text_query.0 = format!(
+        "{:.1}s / {:.1}s  x{}{}  (space: pause, left/right: skip, up/down: speed, esc: menu)",
+        playback.time,
+        duration,
+        playback.speed,
+        if playback.paused { "  paused" } else { "" },
+    );
+    bar_query.width = Val::Percent(100. * playback.time / duration.max(0.01));
+    chat_query.0 = playback.chat.iter().cloned().collect::<Vec<_>>().join("\n");
+}
+
+fn cleanup_playback(
+    mut commands: Commands,
+    mut playback: ResMut<Playback>,
+    playback_query: Query<Entity, With<PlaybackEntity>>,
+) {
+    for entity in &playback_query {
+        commands.entity(entity).despawn();
+    }
+    *playback = Playback::default();
+}
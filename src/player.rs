@@ -1,33 +1,169 @@
-use crate::{menu, twitch, util, AssetHandles, GameState};
+use crate::{
+    attribution, chat_control, goat_stats::GoatStats, hazard, lobby, menu, moderation,
+    spawn::SpawnPlanner, team, twitch, util, AssetHandles, GameState,
+};
 use avian3d::prelude::*;
 use bevy::prelude::*;
 use bevy_mod_billboard::prelude::*;
 use std::collections::HashSet;
 
 pub const SHEEP_SIZE: Vec3 = vec3(2.5, 3.5, 3.5);
+/// how high up goats that join or come back mid round are dropped from
+pub const DROP_HEIGHT: f32 = 60.;
 
 #[derive(Resource, Default)]
 pub struct Players(pub HashSet<String>);
 
+impl Players {
+    pub fn has_viewer(&self, viewer: &str) -> bool {
+        self.0.iter().any(|name| is_owned_by(name, viewer))
+    }
+}
+
+/// the viewer a goat belongs to, twitch names cant have spaces
+pub fn viewer_of(name: &str) -> &str {
+    name.split(' ').next().unwrap_or(name)
+}
+
+/// goats are named "sender" or "sender 3" when theres more than one per player.
+/// twitch names arent case sensitive so neither is this
+pub fn is_owned_by(name: &str, sender: &str) -> bool {
+    match name.to_lowercase().strip_prefix(&sender.to_lowercase()) {
+        Some("") => true,
+        Some(rest) => rest
+            .strip_prefix(' ')
+            .is_some_and(|i| i.parse::<u32>().is_ok()),
+        None => false,
+    }
+}
+
+/// enough about a goat to spawn it again
+#[derive(Clone)]
+pub struct RosterEntry {
+    pub name: String,
+    pub team: Option<team::Team>,
+    pub stats: GoatStats,
+    pub bot: bool,
+}
+
+/// everyone the round started with, so it can be started over
+#[derive(Resource, Default)]
+pub struct Roster(pub Vec<RosterEntry>);
+
+impl Roster {
+    /// how many teams had goats when the round started
+    pub fn teams(&self) -> usize {
+        self.0
+            .iter()
+            .filter_map(|goat| goat.team)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+}
+
 #[derive(Component)]
 pub struct Player(String);
 
+impl Player {
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    pub fn viewer(&self) -> &str {
+        viewer_of(&self.0)
+    }
+
+    pub fn is_owned_by(&self, sender: &str) -> bool {
+        is_owned_by(&self.0, sender)
+    }
+}
+
+#[derive(Component)]
+pub struct Speed(pub f32);
+
+/// new goats get a random top speed in this range
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct GoatSpeed {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for GoatSpeed {
+    fn default() -> Self {
+        GoatSpeed { min: 40., max: 60. }
+    }
+}
+
+/// a goat nobody in chat owns
 #[derive(Component)]
-struct Speed(f32);
+pub struct Bot;
+
+// the mesh child of a goat
+#[derive(Component)]
+pub struct GoatModel;
+
+// the name floating over a goat
+#[derive(Component)]
+pub struct Nameplate;
+
+/// something the goat ai drives towards, weighted against chasing other goats
+#[derive(Component)]
+pub struct AiAttractor(pub f32);
+
+/// an attractor only the goats of this team drive towards
+#[derive(Component)]
+pub struct AttractsTeam(pub team::Team);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DeathCause {
+    Rammed,
+    BlownUp,
+    FellOff,
+    Lava,
+    Flipped,
+}
+
+#[derive(Event)]
+pub struct GoatJoined {
+    pub name: String,
+    pub team: Option<team::Team>,
+}
+
+#[derive(Event)]
+pub struct GoatDied {
+    // already despawned, only good for looking up what was recorded about it
+    pub entity: Entity,
+    pub name: String,
+    pub pos: Vec3,
+    pub killer: Option<String>,
+    pub killer_team: Option<team::Team>,
+    pub cause: DeathCause,
+    // for bringing it back when respawns are on
+    pub goat: RosterEntry,
+}
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Players::default())
+            .insert_resource(Roster::default())
+            .insert_resource(GoatSpeed::default())
+            .add_event::<GoatJoined>()
+            .add_event::<GoatDied>()
             .add_systems(
                 Update,
-                read_user_events.run_if(in_state(GameState::Connected)),
+                read_user_events.run_if(
+                    in_state(GameState::Connected)
+                        .or(in_state(GameState::Spectating).and(late_joins)),
+                ),
             )
+            .add_systems(Update, add_nameplates)
+            .add_systems(OnEnter(GameState::Spectating), remember_roster)
             .add_systems(
                 FixedUpdate,
                 // (/*control_players,*/ kill_players, end).run_if(in_state(GameState::Spectating)),
-                (control_players, kill_players, end).run_if(in_state(GameState::Spectating)),
+                (control_players, kill_players).run_if(in_state(GameState::Spectating)),
             );
     }
 }
@@ -38,43 +174,28 @@ fn spawn_player(
     name: String,
     pos: Vec3,
     rot_angle: f32,
-    speed: f32,
-) {
-    commands.spawn((
+    stats: GoatStats,
+    team: Option<team::Team>,
+) -> Entity {
+    let material = team_material(asset_handles, team);
+    let scale = stats.scale;
+    // big goats start a bit higher so they dont spawn in the ground
+    let lift = SHEEP_SIZE.y / 2. * (scale - 1.).max(0.);
+
+    let mut entity = commands.spawn((
         // Mesh3d(asset_handles.sheep_sized_cuboid.clone().unwrap()),
         // MeshMaterial3d(asset_handles.player_material.clone().unwrap()),
-        Player(name.clone()),
-        Speed(speed),
+        Player(name),
+        Speed(stats.speed),
+        stats,
         Transform::default()
-            .with_translation(pos)
+            .with_translation(pos + Vec3::Y * lift)
             .with_rotation(Quat::from_rotation_y(rot_angle)),
         RigidBody::Dynamic,
-        Collider::compound(vec![
-            (
-                Vec3::ZERO,
-                Quat::default(),
-                Collider::cuboid(SHEEP_SIZE.x, SHEEP_SIZE.y, SHEEP_SIZE.z),
-            ),
-            (vec3(0., 0.7, -2.5), Quat::default(), Collider::sphere(1.25)),
-        ]),
-        ComputedMass::new(100.),
-        ComputedCenterOfMass::new(0., -1.7, 0.),
+        goat_body(&stats),
         Visibility::Inherited,
         children![
-            (
-                Mesh3d(asset_handles.player_mesh.clone().unwrap()),
-                MeshMaterial3d(asset_handles.player_material.clone().unwrap()),
-                Transform::default()
-                    .with_scale(Vec3::splat(0.1))
-                    .with_translation(vec3(0.2, -1.7, 0.))
-                    .with_rotation(Quat::from_rotation_y(std::f32::consts::PI))
-            ),
-            (
-                BillboardText::new(name),
-                Transform::default()
-                    .with_scale(Vec3::splat(0.05))
-                    .with_translation(vec3(0., 4., 0.))
-            ),
+            goat_model(asset_handles, material, scale),
             // (
             //     Mesh3d(asset_handles.the_sphere.clone().unwrap()),
             //     MeshMaterial3d(asset_handles.player_material.clone().unwrap()),
@@ -82,6 +203,165 @@ fn spawn_player(
             // )
         ],
     ));
+    if let Some(team) = team {
+        entity.insert(team);
+    }
+    entity.id()
+}
+
+// the nameplate goes on after spawning so every way a goat spawns gets the filtered name
+fn add_nameplates(
+    mut commands: Commands,
+    name_filter: Res<moderation::NameFilter>,
+    player_query: Query<(Entity, &Player, &GoatStats), Added<Player>>,
+) {
+    for (goat, player, stats) in &player_query {
+        let name = name_filter.display(player.name());
+        let label = match stats.tag() {
+            Some(tag) => format!("{name}\n{tag}"),
+            None => name,
+        };
+        commands
+            .entity(goat)
+            .with_child(nameplate(label, stats.scale));
+    }
+}
+
+fn late_joins(settings: Res<menu::Settings>) -> bool {
+    settings.late_joins
+}
+
+pub fn remember_roster(
+    mut roster: ResMut<Roster>,
+    player_query: Query<(&Player, Option<&team::Team>, &GoatStats, Has<Bot>)>,
+) {
+    roster.0 = player_query
+        .iter()
+        .map(|(player, team, stats, bot)| RosterEntry {
+            name: player.name().to_owned(),
+            team: team.copied(),
+            stats: *stats,
+            bot,
+        })
+        .collect();
+}
+
+/// spawns every goat from the roster again somewhere new
+pub fn respawn_roster(
+    commands: &mut Commands,
+    asset_handles: &Res<AssetHandles>,
+    planner: &SpawnPlanner,
+    roster: &Roster,
+    players: &mut Players,
+) {
+    let mut taken = vec![];
+    for entry in &roster.0 {
+        players.0.insert(entry.name.clone());
+        let pos = planner.spot(&mut taken, entry.team);
+        spawn_entry(commands, asset_handles, planner, entry, pos);
+    }
+}
+
+/// spawns a goat from what was remembered about it
+pub fn spawn_entry(
+    commands: &mut Commands,
+    asset_handles: &Res<AssetHandles>,
+    planner: &SpawnPlanner,
+    entry: &RosterEntry,
+    pos: Vec3,
+) -> Entity {
+    let goat = spawn_player(
+        commands,
+        asset_handles,
+        entry.name.clone(),
+        pos,
+        planner.facing(pos),
+        entry.stats,
+        entry.team,
+    );
+    if entry.bot {
+        commands.entity(goat).insert(Bot);
+    }
+    goat
+}
+
+pub fn team_material(
+    asset_handles: &Res<AssetHandles>,
+    team: Option<team::Team>,
+) -> Handle<StandardMaterial> {
+    match team {
+        Some(team) => asset_handles.team_materials[&team].clone(),
+        None => asset_handles.player_material.clone().unwrap(),
+    }
+}
+
+/// a goat that only looks like one, for replays and recordings
+pub fn ghost_goat(
+    asset_handles: &Res<AssetHandles>,
+    name: String,
+    team: Option<team::Team>,
+    trans: Transform,
+) -> impl Bundle {
+    (
+        trans,
+        Visibility::Inherited,
+        children![
+            goat_model(asset_handles, team_material(asset_handles, team), 1.),
+            nameplate(name, 1.),
+        ],
+    )
+}
+
+/// the collider and mass of a goat with these stats, swapped out when a goat grows
+pub fn goat_body(stats: &GoatStats) -> impl Bundle {
+    let scale = stats.scale;
+    (
+        Collider::compound(vec![
+            (
+                Vec3::ZERO,
+                Quat::default(),
+                Collider::cuboid(
+                    SHEEP_SIZE.x * scale,
+                    SHEEP_SIZE.y * scale,
+                    SHEEP_SIZE.z * scale,
+                ),
+            ),
+            (
+                vec3(0., 0.7, -2.5) * scale,
+                Quat::default(),
+                Collider::sphere(1.25 * scale),
+            ),
+        ]),
+        ComputedMass::new(stats.mass),
+        ComputedCenterOfMass::new(0., -1.7 * scale, 0.),
+    )
+}
+
+pub fn goat_model(
+    asset_handles: &Res<AssetHandles>,
+    material: Handle<StandardMaterial>,
+    scale: f32,
+) -> impl Bundle {
+    (
+        GoatModel,
+        Mesh3d(asset_handles.player_mesh.clone().unwrap()),
+        MeshMaterial3d(material),
+        Transform::default()
+            .with_scale(Vec3::splat(0.1 * scale))
+            .with_translation(vec3(0.2, -1.7, 0.) * scale)
+            .with_rotation(Quat::from_rotation_y(std::f32::consts::PI)),
+    )
+}
+
+// floats over the goat, higher for bigger goats but always the same text size
+pub fn nameplate(name: String, goat_scale: f32) -> impl Bundle {
+    (
+        Nameplate,
+        BillboardText::new(name),
+        Transform::default()
+            .with_scale(Vec3::splat(0.05))
+            .with_translation(vec3(0., 4. * goat_scale, 0.)),
+    )
 }
 
 fn is_to_the_right(transform: &Transform, pos: &Vec3) -> bool {
@@ -89,15 +369,21 @@ fn is_to_the_right(transform: &Transform, pos: &Vec3) -> bool {
     pos.dot(transform.right().into()) > 0.
 }
 
-fn decide_angle(transform: &Transform, all_pos: &Vec<Vec3>) -> f32 {
+// position, team, how much the goat wants to go there and the only team that goes for it
+type Target = (Vec3, Option<team::Team>, f32, Option<team::Team>);
+
+fn decide_angle(transform: &Transform, team: Option<&team::Team>, targets: &Vec<Target>) -> f32 {
     let mut right_score = 0.;
 
-    for pos in all_pos {
-        if pos.distance_squared(transform.translation) < 0.01 {
+    for (pos, other_team, weight, only_for) in targets {
+        if pos.distance_squared(transform.translation) < 0.01
+            || (team.is_some() && team == other_team.as_ref())
+            || only_for.is_some_and(|only_for| team != Some(&only_for))
+        {
             continue;
         }
 
-        let strength = 1. / pos.distance(transform.translation).powi(2);
+        let strength = weight / pos.distance(transform.translation).powi(2);
         let sign = ((is_to_the_right(transform, pos) as i32) * 2 - 1) as f32;
         right_score += strength * sign;
     }
@@ -113,17 +399,45 @@ fn decide_angle(transform: &Transform, all_pos: &Vec<Vec3>) -> f32 {
 }
 
 fn control_players(
-    mut player_query: Query<(&mut LinearVelocity, &mut Transform, &Speed), With<Player>>,
+    time: Res<Time>,
+    mut player_query: Query<
+        (
+            &mut LinearVelocity,
+            &mut Transform,
+            &Speed,
+            &GoatStats,
+            Option<&team::Team>,
+            Option<&mut chat_control::ChatControlled>,
+        ),
+        With<Player>,
+    >,
+    attractor_query: Query<(&Transform, &AiAttractor, Option<&AttractsTeam>), Without<Player>>,
 ) {
-    let mut positions: Vec<Vec3> = vec![];
-    for (_, trans, _) in &player_query {
-        positions.push(trans.translation);
+    let mut targets: Vec<Target> = vec![];
+    for (_, trans, _, _, team, _) in &player_query {
+        targets.push((trans.translation, team.copied(), 1., None));
+    }
+    for (trans, attractor, only_for) in &attractor_query {
+        targets.push((
+            trans.translation,
+            None,
+            attractor.0,
+            only_for.map(|only_for| only_for.0),
+        ));
     }
     let player_acc = 1.;
+    let ram_boost = 30.;
+
+    for (mut linvel, mut trans, max_speed, stats, team, chat_control) in &mut player_query {
+        let chat_input =
+            chat_control.and_then(|mut control| control.take_input(time.elapsed_secs()));
 
-    for (mut linvel, mut trans, max_speed) in &mut player_query {
         // rotate
-        let angle = decide_angle(&trans, &positions);
+        let angle = stats.turn_rate
+            * match &chat_input {
+                Some(input) => input.steer * -0.02,
+                None => decide_angle(&trans, team, &targets),
+            };
         trans.rotate_y(angle);
         let rotated_xz = Mat2::from_angle(angle).mul_vec2(linvel.0.xz());
         linvel.0 = vec3(rotated_xz.x, linvel.0.y, rotated_xz.y);
@@ -132,42 +446,83 @@ fn control_players(
         // linvel.0 += player_acc * trans.forward();
         // let clamped_xz = linvel.0.xz().clamp_length_max(max_speed.0);
         // linvel.0 = vec3(clamped_xz.x, linvel.0.y, clamped_xz.y);
-        if linvel.0.xz().length() < max_speed.0 {
-            linvel.0 += player_acc * trans.forward();
+        let throttle = chat_input.as_ref().is_none_or(|input| input.throttle);
+        if throttle && linvel.0.xz().length() < max_speed.0 {
+            linvel.0 += player_acc * stats.accel * trans.forward();
+        }
+        if chat_input.as_ref().is_some_and(|input| input.ram) {
+            linvel.0 += ram_boost * trans.forward();
         }
 
         // stop them from drifting if theyre on the ground
-        if trans.translation.y < 3. {
-            let adj = trans.right().dot(linvel.0.normalize()) * trans.right() * 3.;
+        if trans.translation.y < 3. * stats.scale.max(1.) {
+            let adj = trans.right().dot(linvel.0.normalize()) * trans.right() * 3. * stats.grip;
             linvel.0 -= adj;
         }
     }
 }
 
-fn kill_players(
+pub fn kill_players(
     mut commands: Commands,
     mut players: ResMut<Players>,
+    mut death_events: EventWriter<GoatDied>,
+    time: Res<Time>,
     asset_handles: Res<AssetHandles>,
-    player_query: Query<(Entity, &Transform, &Player)>,
+    player_query: Query<(
+        Entity,
+        &Transform,
+        &Player,
+        &GoatStats,
+        Option<&team::Team>,
+        Has<Bot>,
+        Option<&attribution::LastHit>,
+        Option<&hazard::Burn>,
+    )>,
 ) {
-    for (entity, trans, name) in player_query {
-        // die if close enough to upside down or outside the bounds
-        if trans.up().dot(Vec3::Y) < 0.15
-            || trans
-                .translation
-                .clamp(Vec3::splat(-300.), Vec3::splat(300.))
-                != trans.translation
-        {
-            players.0.remove(&name.0);
-            commands.entity(entity).despawn();
-            commands.spawn(util::explosion(&asset_handles, trans.translation));
+    for (entity, trans, name, stats, team, bot, last_hit, burn) in player_query {
+        // die if close enough to upside down, outside the bounds or cooked by lava
+        let out_of_bounds = trans
+            .translation
+            .clamp(Vec3::splat(-300.), Vec3::splat(300.))
+            != trans.translation;
+        let cooked = burn.is_some_and(|burn| burn.0 >= hazard::LAVA_LETHAL_SECS);
+        if trans.up().dot(Vec3::Y) >= 0.15 && !cooked && !out_of_bounds {
+            continue;
         }
-    }
-}
 
-fn end(player_query: Query<&Player>, mut next_game_state: ResMut<NextState<crate::GameState>>) {
-    if player_query.iter().len() == 1 {
-        next_game_state.set(crate::GameState::End);
+        players.0.remove(&name.0);
+        commands.entity(entity).despawn();
+        commands.spawn(util::explosion(&asset_handles, trans.translation));
+
+        let attacker = last_hit
+            .filter(|hit| hit.is_recent(time.elapsed_secs()))
+            .map(|hit| &hit.attacker);
+        let (killer, killer_team) = match attacker {
+            Some(attribution::Attacker::Goat { name, team }) => (Some(name.clone()), *team),
+            _ => (None, None),
+        };
+        // the streamer gets the kill however the goat ends up dying
+        let cause = match attacker {
+            Some(attribution::Attacker::Streamer) => DeathCause::BlownUp,
+            _ if cooked => DeathCause::Lava,
+            _ if out_of_bounds => DeathCause::FellOff,
+            Some(attribution::Attacker::Goat { .. }) => DeathCause::Rammed,
+            None => DeathCause::Flipped,
+        };
+        death_events.write(GoatDied {
+            entity,
+            name: name.0.clone(),
+            pos: trans.translation,
+            killer,
+            killer_team,
+            cause,
+            goat: RosterEntry {
+                name: name.0.clone(),
+                team: team.copied(),
+                stats: *stats,
+                bot,
+            },
+        });
     }
 }
 
@@ -177,60 +532,156 @@ fn read_user_events(
     settings: Res<menu::Settings>,
     asset_handles: Res<AssetHandles>,
     mut players: ResMut<Players>,
-    input: Res<ButtonInput<KeyCode>>,
+    ban_list: Res<lobby::BanList>,
+    mut queue: ResMut<lobby::JoinQueue>,
+    goat_speed: Res<GoatSpeed>,
+    state: Res<State<GameState>>,
+    planner: SpawnPlanner,
+    mut join_events: EventWriter<GoatJoined>,
+    goat_query: Query<&Transform, With<Player>>,
 ) {
+    let mut taken = goat_query
+        .iter()
+        .map(|trans| trans.translation)
+        .collect::<Vec<_>>();
+    // joining mid round drops the goat in from the sky
+    let drop_in = *state.get() == GameState::Spectating;
     for event in events.read() {
-        let msg = event.0.clone();
-        for i in 0..settings.goats_per_player {
-            let name = if settings.goats_per_player == 1 {
-                msg.sender.clone()
-            } else {
-                msg.sender.clone() + " " + &i.to_string()
-            };
-
-            if players.0.contains(&name)
-                || (settings.filter_joins && msg.text != "!play".to_owned())
-            {
-                continue;
+        let msg = &event.0;
+        let is_join = msg.text == "!play"
+            || (settings.team_mode == team::TeamMode::Chosen
+                && team::parse_choice(&msg.text).is_some());
+        if ban_list.contains(&msg.sender) || (settings.filter_joins && !is_join) {
+            continue;
+        }
+        // everyone who doesnt fit waits for the next round
+        if is_full(&settings, &players) {
+            if !players.has_viewer(&msg.sender) {
+                queue.push(msg.clone());
             }
-            players.0.insert(name.clone());
-            let pos = vec3(
-                rand::random_range(-300.0..300.0),
-                3.,
-                rand::random_range(-300.0..300.0),
-            );
-            spawn_player(
-                &mut commands,
-                &asset_handles,
-                name.clone(),
-                pos,
-                rand::random_range(0.0..std::f32::consts::TAU),
-                rand::random_range(40.0..60.0),
-            );
+            continue;
+        }
+        if let Some(joined) = spawn_viewer(
+            &mut commands,
+            &asset_handles,
+            &settings,
+            &mut players,
+            &goat_speed,
+            &planner,
+            &mut taken,
+            msg,
+            drop_in,
+        ) {
+            join_events.write(joined);
         }
     }
+}
 
-    if input.pressed(KeyCode::Space) {
-        let mut name = "mrrow ".to_owned();
-        for i in 2..999999 {
-            if !players.0.contains(&(name.clone() + &i.to_string())) {
-                name = name.clone() + &i.to_string();
-                players.0.insert(name.clone());
-                break;
-            }
+/// spawns the goats of a viewer that arent there yet, as many as still fit.
+/// taken is where the goats already are, with drop_in they fall in from above
+pub fn spawn_viewer(
+    commands: &mut Commands,
+    asset_handles: &Res<AssetHandles>,
+    settings: &menu::Settings,
+    players: &mut Players,
+    goat_speed: &GoatSpeed,
+    planner: &SpawnPlanner,
+    taken: &mut Vec<Vec3>,
+    msg: &twitch::Message,
+    drop_in: bool,
+) -> Option<GoatJoined> {
+    let team = team::assign(settings, Some(msg));
+    let mut joined = false;
+    for i in 0..settings.goats_per_player {
+        let name = if settings.goats_per_player == 1 {
+            msg.sender.clone()
+        } else {
+            msg.sender.clone() + " " + &i.to_string()
+        };
+
+        if players.0.contains(&name) {
+            continue;
         }
-        let pos = vec3(
-            rand::random_range(-300.0..300.0),
-            3.,
-            rand::random_range(-300.0..300.0),
-        );
+        if is_full(settings, players) {
+            break;
+        }
+        players.0.insert(name.clone());
+        joined = true;
+        let pos = planner.spot(taken, team);
+        let height = if drop_in { DROP_HEIGHT } else { pos.y };
+        let stats = GoatStats::roll(settings.goat_stats, Some(&name), goat_speed);
         spawn_player(
-            &mut commands,
-            &asset_handles,
+            commands,
+            asset_handles,
             name,
-            pos,
-            rand::random_range(0.0..std::f32::consts::TAU),
-            rand::random_range(40.0..60.0),
+            vec3(pos.x, height, pos.z),
+            planner.facing(pos),
+            stats,
+            team,
         );
     }
+    joined.then(|| GoatJoined {
+        name: msg.sender.clone(),
+        team,
+    })
+}
+
+/// true once theres as many goats as the settings allow
+pub fn is_full(settings: &menu::Settings, players: &Players) -> bool {
+    settings
+        .max_goats
+        .is_some_and(|max| players.0.len() as u32 >= max)
+}
+
+/// adds a goat nobody owns, named prefix and a number, with drop_in it falls in from above
+pub fn spawn_bot(
+    commands: &mut Commands,
+    asset_handles: &Res<AssetHandles>,
+    settings: &menu::Settings,
+    players: &mut Players,
+    goat_speed: &GoatSpeed,
+    planner: &SpawnPlanner,
+    taken: &mut Vec<Vec3>,
+    prefix: &str,
+    drop_in: bool,
+) -> GoatJoined {
+    let mut name = prefix.to_owned() + " ";
+    for i in 2..999999 {
+        if !players.0.contains(&(name.clone() + &i.to_string())) {
+            name = name.clone() + &i.to_string();
+            players.0.insert(name.clone());
+            break;
+        }
+    }
+    let team = team::assign(settings, None);
+    let pos = planner.spot(taken, team);
+    let height = if drop_in { DROP_HEIGHT } else { pos.y };
+    let goat = spawn_player(
+        commands,
+        asset_handles,
+        name.clone(),
+        vec3(pos.x, height, pos.z),
+        planner.facing(pos),
+        GoatStats::roll(settings.goat_stats, None, goat_speed),
+        team,
+    );
+    commands.entity(goat).insert(Bot);
+    GoatJoined { name, team }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn goats_belong_to_their_viewer() {
+        assert!(is_owned_by("someone", "someone"));
+        assert!(is_owned_by("someone 3", "someone"));
+        assert!(is_owned_by("SomeOne 3", "someone"));
+        assert!(is_owned_by("someone", "SomeOne"));
+        assert!(!is_owned_by("someone else", "someone"));
+        assert!(!is_owned_by("someones", "someone"));
+        assert!(!is_owned_by("someone's goat 2", "someone"));
+        assert_eq!(viewer_of("someone 3"), "someone");
+    }
 }
@@ -0,0 +1,316 @@
+use crate::{attribution, game_mode, player, team::Team, AssetHandles, GameState};
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use bevy_mod_billboard::prelude::*;
+use std::collections::HashSet;
+
+const SPAWN_EVERY_SECS: f32 = 8.;
+const PICKUP_LIFETIME_SECS: f32 = 20.;
+const EFFECT_SECS: f32 = 10.;
+
+pub struct PowerUpPlugin;
+
+impl Plugin for PowerUpPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PowerUpSpawner(Timer::from_seconds(
+            SPAWN_EVERY_SECS,
+            TimerMode::Repeating,
+        )))
+        .add_systems(
+            Update,
+            (spawn_powerups, despawn_old_pickups).run_if(in_state(GameState::Spectating)),
+        )
+        .add_systems(
+            FixedUpdate,
+            (pick_up, run_effects, horn_rams, expire_effects)
+                .chain()
+                .run_if(in_state(GameState::Spectating)),
+        );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PowerUpKind {
+    Speed,
+    Shield,
+    Mass,
+    Horns,
+    Jetpack,
+}
+
+impl PowerUpKind {
+    const ALL: [PowerUpKind; 5] = [
+        PowerUpKind::Speed,
+        PowerUpKind::Shield,
+        PowerUpKind::Mass,
+        PowerUpKind::Horns,
+        PowerUpKind::Jetpack,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            PowerUpKind::Speed => "SPEED",
+            PowerUpKind::Shield => "SHIELD",
+            PowerUpKind::Mass => "CHONK",
+            PowerUpKind::Horns => "HORNS",
+            PowerUpKind::Jetpack => "JETPACK",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            PowerUpKind::Speed => Color::srgb(0.3, 0.8, 1.),
+            PowerUpKind::Shield => Color::srgb(0.9, 0.9, 0.9),
+            PowerUpKind::Mass => Color::srgb(0.6, 0.4, 0.2),
+            PowerUpKind::Horns => Color::srgb(1., 0.2, 0.2),
+            PowerUpKind::Jetpack => Color::srgb(1., 0.6, 0.),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct PowerUpSpawner(Timer);
+
+#[derive(Component)]
+#[require(Sensor, CollidingEntities)]
+struct PowerUp {
+    kind: PowerUpKind,
+    lifetime: Timer,
+}
+
+/// power-up a goat is currently using, goats only carry one at a time
+#[derive(Component)]
+pub struct ActivePowerUp {
+    pub kind: PowerUpKind,
+    timer: Timer,
+    // speed or mass from before the power-up so it can be put back
+    before: f32,
+}
+
+impl ActivePowerUp {
+    /// a shield that didnt come from a pickup
+    pub fn shield() -> Self {
+        ActivePowerUp {
+            kind: PowerUpKind::Shield,
+            timer: Timer::from_seconds(EFFECT_SECS, TimerMode::Once),
+            before: 0.,
+        }
+    }
+
+    pub fn is(&self, kind: PowerUpKind) -> bool {
+        self.kind == kind
+    }
+
+    /// for when the goat gets heavier while it has the power-up, so running out doesnt undo that too
+    pub fn scale_mass(&mut self, factor: f32) {
+        if self.kind == PowerUpKind::Mass {
+            self.before *= factor;
+        }
+    }
+
+    /// puts back what the power-up changed, for when it runs out or gets replaced
+    pub fn undo(&self, speed: &mut player::Speed, mass: &mut ComputedMass) {
+        match self.kind {
+            PowerUpKind::Speed => speed.0 = self.before,
+            PowerUpKind::Mass => *mass = ComputedMass::new(self.before),
+            _ => {}
+        }
+    }
+}
+
+fn spawn_powerups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut spawner: ResMut<PowerUpSpawner>,
+    asset_handles: Res<AssetHandles>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    spawner.0.tick(time.delta());
+    if !spawner.0.just_finished() {
+        return;
+    }
+
+    let kind = PowerUpKind::ALL[rand::random_range(0..PowerUpKind::ALL.len())];
+    let pos = vec3(
+        rand::random_range(-250.0..250.0),
+        3.,
+        rand::random_range(-250.0..250.0),
+    );
+    commands.spawn((
+        game_mode::RoundProp,
+        PowerUp {
+            kind,
+            lifetime: Timer::from_seconds(PICKUP_LIFETIME_SECS, TimerMode::Once),
+        },
+        player::AiAttractor(3.),
+        Collider::sphere(1.5),
+        Transform::from_translation(pos).with_scale(Vec3::splat(2.)),
+        Mesh3d(asset_handles.the_sphere.clone().unwrap()),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: kind.color(),
+            unlit: true,
+            ..Default::default()
+        })),
+        children![(
+            BillboardText::new(kind.label()),
+            Transform::default()
+                .with_scale(Vec3::splat(0.03))
+                .with_translation(vec3(0., 2.5, 0.))
+        )],
+    ));
+}
+
+fn despawn_old_pickups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut powerup_query: Query<(Entity, &mut PowerUp)>,
+) {
+    for (entity, mut powerup) in &mut powerup_query {
+        if powerup.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn pick_up(
+    mut commands: Commands,
+    powerup_query: Query<(Entity, &PowerUp, &CollidingEntities)>,
+    mut player_query: Query<
+        (&mut player::Speed, &mut ComputedMass),
+        (With<player::Player>, Without<ActivePowerUp>),
+    >,
+) {
+    // the inserts only happen after this, so a goat touching two pickups would still pass the filter for the second
+    let mut picked = HashSet::new();
+    for (entity, powerup, colliding) in &powerup_query {
+        let Some(goat) = colliding
+            .iter()
+            .copied()
+            .find(|goat| !picked.contains(goat) && player_query.contains(*goat))
+        else {
+            continue;
+        };
+        picked.insert(goat);
+        let Ok((mut speed, mut mass)) = player_query.get_mut(goat) else {
+            continue;
+        };
+
+        let before = match powerup.kind {
+            PowerUpKind::Speed => {
+                let before = speed.0;
+                speed.0 *= 1.6;
+                before
+            }
+            PowerUpKind::Mass => {
+                let before = mass.value();
+                *mass = ComputedMass::new(before * 3.);
+                before
+            }
+            _ => 0.,
+        };
+
+        commands.entity(goat).insert(ActivePowerUp {
+            kind: powerup.kind,
+            timer: Timer::from_seconds(EFFECT_SECS, TimerMode::Once),
+            before,
+        });
+        commands.entity(entity).despawn();
+    }
+}
+
+fn run_effects(mut player_query: Query<(&ActivePowerUp, &Transform, &mut LinearVelocity)>) {
+    let jetpack_ceiling = 25.;
+
+    for (powerup, trans, mut linvel) in &mut player_query {
+        if powerup.is(PowerUpKind::Jetpack) && trans.translation.y < jetpack_ceiling {
+            linvel.0.y += 2.5;
+        }
+    }
+}
+
+// horns make rams hit twice as hard, the ram itself already pushed once so
+// the same push is added again when two goats first touch
+fn horn_rams(
+    collisions: Collisions,
+    mut hit_events: EventWriter<attribution::Hit>,
+    // goat pairs that were already touching last step
+    mut touching: Local<HashSet<(Entity, Entity)>>,
+    mut player_query: Query<(
+        &Transform,
+        &mut LinearVelocity,
+        &ComputedMass,
+        &player::Player,
+        Option<&Team>,
+        Option<&ActivePowerUp>,
+    )>,
+) {
+    let mut now_touching = HashSet::new();
+    for contacts in collisions.iter() {
+        if !contacts.is_touching() {
+            continue;
+        }
+        let pair = (contacts.collider1, contacts.collider2);
+        if !player_query.contains(pair.0) || !player_query.contains(pair.1) {
+            continue;
+        }
+        now_touching.insert(pair);
+        if touching.contains(&pair) {
+            continue;
+        }
+
+        for (rammer, victim) in [pair, (pair.1, pair.0)] {
+            let Ok((rammer_trans, rammer_vel, _, rammer_player, rammer_team, Some(powerup))) =
+                player_query.get(rammer)
+            else {
+                continue;
+            };
+            let Ok((victim_trans, _, victim_mass, _, _, victim_powerup)) = player_query.get(victim)
+            else {
+                continue;
+            };
+            let dir = (victim_trans.translation - rammer_trans.translation).normalize_or_zero();
+            // only the goat doing the ramming, not the one getting rammed
+            if !powerup.is(PowerUpKind::Horns)
+                || rammer_vel.0.dot(dir) <= 0.
+                || victim_powerup.is_some_and(|powerup| powerup.is(PowerUpKind::Shield))
+            {
+                continue;
+            }
+            let strength = contacts.total_normal_impulse_magnitude() / victim_mass.value().max(1.);
+            let attacker = attribution::Attacker::Goat {
+                name: rammer_player.name().to_owned(),
+                team: rammer_team.copied(),
+            };
+
+            if let Ok((_, mut victim_vel, ..)) = player_query.get_mut(victim) {
+                victim_vel.0 += dir * strength;
+            }
+            hit_events.write(attribution::Hit {
+                victim,
+                attacker,
+                strength,
+            });
+        }
+    }
+    *touching = now_touching;
+}
+
+fn expire_effects(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut player_query: Query<(
+        Entity,
+        &mut ActivePowerUp,
+        &mut player::Speed,
+        &mut ComputedMass,
+    )>,
+) {
+    for (entity, mut powerup, mut speed, mut mass) in &mut player_query {
+        if !powerup.timer.tick(time.delta()).finished() {
+            continue;
+        }
+
+        powerup.undo(&mut speed, &mut mass);
+        commands.entity(entity).remove::<ActivePowerUp>();
+    }
+}
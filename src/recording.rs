@@ -0,0 +1,480 @@
+use crate::{goat_stats::GoatStats, gun, player, team::Team, twitch, util, GameState};
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+// 20 snapshots a second is plenty since playback interpolates between them
+const RECORD_EVERY_SECS: f32 = 0.05;
+pub const RECORDINGS_DIR: &str = "recordings";
+const MAGIC: &[u8; 7] = b"GOATREC";
+const VERSION: u8 = 1;
+// the tick count comes from the file, so a broken one cant make it reserve gigabytes. an hour of ticks
+const MAX_RESERVED_TICKS: u32 = 72_000;
+
+pub struct RecordingPlugin;
+
+impl Plugin for RecordingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Recorder::default())
+            .add_systems(OnEnter(GameState::Spectating), start_recording)
+            .add_systems(
+                FixedUpdate,
+                record_tick.run_if(in_state(GameState::Spectating)),
+            )
+            .add_systems(OnEnter(GameState::End), save_recording);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GoatFrame {
+    pub id: u32,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    // big goats grow during the round
+    pub scale: f32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum MatchEvent {
+    Spawn {
+        id: u32,
+        name: String,
+        team: Option<Team>,
+    },
+    Death {
+        id: u32,
+    },
+    Bullet {
+        pos: Vec3,
+        velocity: Vec3,
+    },
+    Explosion {
+        pos: Vec3,
+    },
+    Chat {
+        sender: String,
+        text: String,
+    },
+}
+
+/// every goat at one point in time and what happened since the tick before
+#[derive(Clone, Debug)]
+pub struct Tick {
+    pub time: f32,
+    pub goats: Vec<GoatFrame>,
+    pub events: Vec<MatchEvent>,
+}
+
+/// a whole round, saved as `recordings/round-<unix time>.goats`
+#[derive(Clone, Debug, Default)]
+pub struct MatchRecording {
+    pub ticks: Vec<Tick>,
+}
+
+impl MatchRecording {
+    pub fn duration(&self) -> f32 {
+        match (self.ticks.first(), self.ticks.last()) {
+            (Some(first), Some(last)) => last.time - first.time,
+            _ => 0.,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut bytes = vec![];
+        self.write_to(&mut bytes)?;
+        std::fs::write(path, bytes)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Self::read_from(&mut bytes.as_slice())
+    }
+
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        w.write_all(&(self.ticks.len() as u32).to_le_bytes())?;
+
+        for tick in &self.ticks {
+            write_f32(w, tick.time)?;
+            w.write_all(&(tick.goats.len() as u16).to_le_bytes())?;
+            for goat in &tick.goats {
+                w.write_all(&goat.id.to_le_bytes())?;
+                write_vec3(w, goat.translation)?;
+                write_quat(w, goat.rotation)?;
+                write_f32(w, goat.scale)?;
+            }
+
+            w.write_all(&(tick.events.len() as u16).to_le_bytes())?;
+            for event in &tick.events {
+                match event {
+                    MatchEvent::Spawn { id, name, team } => {
+                        w.write_all(&[0])?;
+                        w.write_all(&id.to_le_bytes())?;
+                        write_str(w, name)?;
+                        let team = team.map_or(0, |team| {
+                            Team::ALL.iter().position(|t| *t == team).unwrap() as u8 + 1
+                        });
+                        w.write_all(&[team])?;
+                    }
+                    MatchEvent::Death { id } => {
+                        w.write_all(&[1])?;
+                        w.write_all(&id.to_le_bytes())?;
+                    }
+                    MatchEvent::Bullet { pos, velocity } => {
+                        w.write_all(&[2])?;
+                        write_vec3(w, *pos)?;
+                        write_vec3(w, *velocity)?;
+                    }
+                    MatchEvent::Explosion { pos } => {
+                        w.write_all(&[3])?;
+                        write_vec3(w, *pos)?;
+                    }
+                    MatchEvent::Chat { sender, text } => {
+                        w.write_all(&[4])?;
+                        write_str(w, sender)?;
+                        write_str(w, text)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        let mut magic = [0; 7];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_u8(r)? != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a goat recording or from a different version",
+            ));
+        }
+
+        let tick_count = read_u32(r)?;
+        let mut ticks = Vec::with_capacity(tick_count.min(MAX_RESERVED_TICKS) as usize);
+        for _ in 0..tick_count {
+            let time = read_f32(r)?;
+            let goat_count = read_u16(r)?;
+            let mut goats = Vec::with_capacity(goat_count as usize);
+            for _ in 0..goat_count {
+                goats.push(GoatFrame {
+                    id: read_u32(r)?,
+                    translation: read_vec3(r)?,
+                    rotation: read_quat(r)?,
+                    scale: read_f32(r)?,
+                });
+            }
+
+            let event_count = read_u16(r)?;
+            let mut events = Vec::with_capacity(event_count as usize);
+            for _ in 0..event_count {
+                let event = match read_u8(r)? {
+                    0 => MatchEvent::Spawn {
+                        id: read_u32(r)?,
+                        name: read_str(r)?,
+                        team: match read_u8(r)? {
+                            0 => None,
+                            i => Team::ALL.get(i as usize - 1).copied(),
+                        },
+                    },
+                    1 => MatchEvent::Death { id: read_u32(r)? },
+                    2 => MatchEvent::Bullet {
+                        pos: read_vec3(r)?,
+                        velocity: read_vec3(r)?,
+                    },
+                    3 => MatchEvent::Explosion { pos: read_vec3(r)? },
+                    4 => MatchEvent::Chat {
+                        sender: read_str(r)?,
+                        text: read_str(r)?,
+                    },
+                    _ => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "unknown event in recording",
+                        ))
+                    }
+                };
+                events.push(event);
+            }
+
+            ticks.push(Tick {
+                time,
+                goats,
+                events,
+            });
+        }
+
+        Ok(MatchRecording { ticks })
+    }
+}
+
+fn write_f32(w: &mut impl Write, v: f32) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn write_vec3(w: &mut impl Write, v: Vec3) -> io::Result<()> {
+    for x in v.to_array() {
+        write_f32(w, x)?;
+    }
+    Ok(())
+}
+
+// rotations are unit length so 16 bits per component is enough
+fn write_quat(w: &mut impl Write, q: Quat) -> io::Result<()> {
+    for x in q.normalize().to_array() {
+        w.write_all(&((x * i16::MAX as f32) as i16).to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn write_str(w: &mut impl Write, s: &str) -> io::Result<()> {
+    let bytes = &s.as_bytes()[..s.len().min(u16::MAX as usize)];
+    w.write_all(&(bytes.len() as u16).to_le_bytes())?;
+    w.write_all(bytes)
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u16(r: &mut impl Read) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    r.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_f32(r: &mut impl Read) -> io::Result<f32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(f32::from_le_bytes(buf))
+}
+
+fn read_vec3(r: &mut impl Read) -> io::Result<Vec3> {
+    Ok(vec3(read_f32(r)?, read_f32(r)?, read_f32(r)?))
+}
+
+fn read_quat(r: &mut impl Read) -> io::Result<Quat> {
+    let mut xyzw = [0.; 4];
+    for x in &mut xyzw {
+        let mut buf = [0; 2];
+        r.read_exact(&mut buf)?;
+        *x = i16::from_le_bytes(buf) as f32 / i16::MAX as f32;
+    }
+    Ok(Quat::from_array(xyzw).normalize())
+}
+
+fn read_str(r: &mut impl Read) -> io::Result<String> {
+    let mut buf = vec![0; read_u16(r)? as usize];
+    r.read_exact(&mut buf)?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// the newest file in the recordings folder
+pub fn latest_recording() -> Option<PathBuf> {
+    std::fs::read_dir(RECORDINGS_DIR)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "goats"))
+        .max()
+}
+
+#[derive(Resource, Default)]
+struct Recorder {
+    recording: MatchRecording,
+    // events since the last tick
+    pending: Vec<MatchEvent>,
+    since_tick: f32,
+    // bevy reuses entity indices so every goat gets its own id for the recording
+    ids: HashMap<Entity, u32>,
+    next_id: u32,
+}
+
+fn start_recording(mut recorder: ResMut<Recorder>) {
+    *recorder = Recorder {
+        since_tick: RECORD_EVERY_SECS,
+        ..Default::default()
+    };
+}
+
+fn record_tick(
+    time: Res<Time>,
+    mut recorder: ResMut<Recorder>,
+    mut chat_events: EventReader<twitch::ChatMessage>,
+    mut removed_players: RemovedComponents<player::Player>,
+    spawned_query: Query<(Entity, &player::Player, Option<&Team>), Added<player::Player>>,
+    bullet_query: Query<(&Transform, &LinearVelocity), Added<gun::Bullet>>,
+    explosion_query: Query<&Transform, Added<util::Explosion>>,
+    player_query: Query<(Entity, &Transform, &GoatStats), With<player::Player>>,
+) {
+    // events are collected every fixed step so none get lost between ticks,
+    // deaths first so a goat dying the same step another spawns plays back in the right order
+    for entity in removed_players.read() {
+        if let Some(id) = recorder.ids.remove(&entity) {
+            recorder.pending.push(MatchEvent::Death { id });
+        }
+    }
+    for (entity, player, team) in &spawned_query {
+        let id = recorder.next_id;
+        recorder.next_id += 1;
+        recorder.ids.insert(entity, id);
+        recorder.pending.push(MatchEvent::Spawn {
+            id,
+            name: player.name().to_owned(),
+            team: team.copied(),
+        });
+    }
+    for (trans, linvel) in &bullet_query {
+        recorder.pending.push(MatchEvent::Bullet {
+            pos: trans.translation,
+            velocity: linvel.0,
+        });
+    }
+    for trans in &explosion_query {
+        recorder.pending.push(MatchEvent::Explosion {
+            pos: trans.translation,
+        });
+    }
+    for event in chat_events.read() {
+        recorder.pending.push(MatchEvent::Chat {
+            sender: event.0.display_name.clone(),
+            text: event.0.text.clone(),
+        });
+    }
+
+    recorder.since_tick += time.delta_secs();
+    if recorder.since_tick < RECORD_EVERY_SECS {
+        return;
+    }
+    recorder.since_tick = 0.;
+
+    let goats = player_query
+        .iter()
+        .filter_map(|(entity, trans, stats)| {
+            Some(GoatFrame {
+                id: *recorder.ids.get(&entity)?,
+                translation: trans.translation,
+                rotation: trans.rotation,
+                scale: stats.scale,
+            })
+        })
+        .collect();
+    let events = std::mem::take(&mut recorder.pending);
+    recorder.recording.ticks.push(Tick {
+        time: time.elapsed_secs(),
+        goats,
+        events,
+    });
+}
+
+fn save_recording(recorder: Res<Recorder>) {
+    if recorder.recording.ticks.is_empty() {
+        return;
+    }
+
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let path = Path::new(RECORDINGS_DIR).join(format!("round-{secs}.goats"));
+    let saved =
+        std::fs::create_dir_all(RECORDINGS_DIR).and_then(|_| recorder.recording.save(&path));
+    match saved {
+        Ok(()) => info!("saved round to {}", path.display()),
+        Err(err) => error!("couldnt save the round recording: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_reads_back_what_was_written() {
+        let recording = MatchRecording {
+            ticks: vec![
+                Tick {
+                    time: 1.5,
+                    goats: vec![GoatFrame {
+                        id: 0,
+                        translation: vec3(1., 2., 3.),
+                        rotation: Quat::from_rotation_y(1.),
+                        scale: 2.,
+                    }],
+                    events: vec![
+                        MatchEvent::Spawn {
+                            id: 0,
+                            name: "someone".to_owned(),
+                            team: Some(Team::Blue),
+                        },
+                        MatchEvent::Chat {
+                            sender: "someone".to_owned(),
+                            text: "hi".to_owned(),
+                        },
+                    ],
+                },
+                Tick {
+                    time: 1.55,
+                    goats: vec![],
+                    events: vec![
+                        MatchEvent::Death { id: 0 },
+                        MatchEvent::Spawn {
+                            id: 1,
+                            name: "someone else".to_owned(),
+                            team: None,
+                        },
+                        MatchEvent::Bullet {
+                            pos: Vec3::Y,
+                            velocity: Vec3::NEG_Z * 1000.,
+                        },
+                        MatchEvent::Explosion { pos: Vec3::X },
+                    ],
+                },
+            ],
+        };
+
+        let mut bytes = vec![];
+        recording.write_to(&mut bytes).unwrap();
+        let read = MatchRecording::read_from(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(read.ticks.len(), 2);
+        for (read, written) in read.ticks.iter().zip(&recording.ticks) {
+            assert_eq!(read.time, written.time);
+            assert_eq!(read.events, written.events);
+            assert_eq!(read.goats.len(), written.goats.len());
+            for (read, written) in read.goats.iter().zip(&written.goats) {
+                assert_eq!(read.id, written.id);
+                assert_eq!(read.translation, written.translation);
+                assert_eq!(read.scale, written.scale);
+                // rotations are stored in 16 bits
+                assert!(read.rotation.abs_diff_eq(written.rotation, 1e-3));
+            }
+        }
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut bytes = vec![];
+        MatchRecording::default().write_to(&mut bytes).unwrap();
+        bytes[MAGIC.len()] = VERSION + 1;
+        assert!(MatchRecording::read_from(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn broken_tick_counts_dont_reserve_everything() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend(u32::MAX.to_le_bytes());
+        assert!(MatchRecording::read_from(&mut bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,348 @@
+use crate::{
+    feed, goat_stats::GoatStats, gun, lobby, menu, player, powerup, spawn::SpawnPlanner, twitch,
+    AssetHandles, GameState,
+};
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// big goats grow this much, up to MAX_SCALE
+const GROWTH: f32 = 2.;
+const MAX_SCALE: f32 = 3.;
+// airstrikes fall from this high, fast enough to give the goat half a second to get away
+const BOMB_HEIGHT: f32 = 80.;
+const BOMB_SPEED: f32 = 150.;
+
+pub struct RedeemPlugin;
+
+impl Plugin for RedeemPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Redemptions::default())
+            .add_event::<Redeemed>()
+            .add_systems(Update, (read_redemptions, apply_redemptions).chain());
+    }
+}
+
+/// what a channel point reward or a cheer does in the game
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum RedeemAction {
+    // makes the goat bigger and heavier for the rest of the round
+    BigGoat,
+    // a bomb on the named goat or a random one
+    Airstrike,
+    // a shield like the power-up
+    Shield,
+    // this many extra goats fall in, named after whoever redeemed it
+    DropGoats(u32),
+}
+
+impl RedeemAction {
+    pub fn label(self) -> String {
+        match self {
+            RedeemAction::BigGoat => "a big goat".to_owned(),
+            RedeemAction::Airstrike => "an airstrike".to_owned(),
+            RedeemAction::Shield => "a shield".to_owned(),
+            RedeemAction::DropGoats(goats) => format!("{goats} extra goats"),
+        }
+    }
+}
+
+/// a channel point reward, the id shows up in the log the first time someone redeems it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Reward {
+    pub id: String,
+    pub action: RedeemAction,
+}
+
+/// cheers of at least this many bits
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Cheer {
+    pub bits: u64,
+    pub action: RedeemAction,
+}
+
+/// what rewards and cheers do, from config.ron
+#[derive(Resource, Default)]
+pub struct Redemptions {
+    pub rewards: Vec<Reward>,
+    pub cheers: Vec<Cheer>,
+}
+
+impl Redemptions {
+    fn action(&self, msg: &twitch::Message) -> Option<RedeemAction> {
+        if let Some(id) = &msg.reward {
+            let reward = self.rewards.iter().find(|reward| reward.id == *id);
+            if reward.is_none() {
+                info!(
+                    "{} redeemed reward {id}, add it to rewards in config.ron to make it do something",
+                    msg.sender
+                );
+            }
+            return reward.map(|reward| reward.action);
+        }
+        // the biggest cheer the bits are enough for
+        self.cheers
+            .iter()
+            .filter(|cheer| msg.bits > 0 && msg.bits >= cheer.bits)
+            .max_by_key(|cheer| cheer.bits)
+            .map(|cheer| cheer.action)
+    }
+}
+
+/// someone paid for something to happen, the text can name the goat it happens to
+#[derive(Event, Clone, Debug)]
+pub struct Redeemed {
+    pub viewer: String,
+    pub action: RedeemAction,
+    pub text: String,
+}
+
+fn read_redemptions(
+    redemptions: Res<Redemptions>,
+    ban_list: Res<lobby::BanList>,
+    mut chat_events: EventReader<twitch::ChatMessage>,
+    mut redeem_events: EventWriter<Redeemed>,
+) {
+    for event in chat_events.read() {
+        if ban_list.contains(&event.0.sender) {
+            continue;
+        }
+        if let Some(action) = redemptions.action(&event.0) {
+            redeem_events.write(Redeemed {
+                viewer: event.0.sender.clone(),
+                action,
+                text: event.0.text.clone(),
+            });
+        }
+    }
+}
+
+fn apply_redemptions(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    asset_handles: Res<AssetHandles>,
+    settings: Res<menu::Settings>,
+    goat_speed: Res<player::GoatSpeed>,
+    mut players: ResMut<player::Players>,
+    planner: SpawnPlanner,
+    mut redeem_events: EventReader<Redeemed>,
+    mut feed_events: EventWriter<feed::FeedEvent>,
+    mut join_events: EventWriter<player::GoatJoined>,
+    mut goat_query: Query<(
+        Entity,
+        &player::Player,
+        &mut Transform,
+        &LinearVelocity,
+        &mut GoatStats,
+        &mut player::Speed,
+        &mut ComputedMass,
+        Option<&mut powerup::ActivePowerUp>,
+    )>,
+    // the model and the nameplate grow with the goat but the root doesnt, so the text stays the same size
+    mut part_query: Query<
+        (&mut Transform, &ChildOf, Has<player::Nameplate>),
+        (
+            Or<(With<player::GoatModel>, With<player::Nameplate>)>,
+            Without<player::Player>,
+        ),
+    >,
+) {
+    for event in redeem_events.read() {
+        // between rounds theres no goats to do anything to
+        if *state.get() != GameState::Spectating {
+            info!(
+                "{} redeemed {} between rounds",
+                event.viewer,
+                event.action.label()
+            );
+            continue;
+        }
+
+        // chatters type @name or just name but any text that isnt a goat is ignored
+        let named = event
+            .text
+            .split_whitespace()
+            .next()
+            .map(|word| word.trim_start_matches('@').to_lowercase())
+            .filter(|word| {
+                goat_query
+                    .iter()
+                    .any(|(_, player, ..)| player.is_owned_by(word))
+            });
+        // the named goats, otherwise the goats of whoever redeemed it
+        let owner = named.as_deref().unwrap_or(&event.viewer);
+        let targets = goat_query
+            .iter()
+            .filter(|(_, player, ..)| player.is_owned_by(owner))
+            .map(|(goat, ..)| goat)
+            .collect::<Vec<_>>();
+
+        match event.action {
+            RedeemAction::BigGoat => {
+                for goat in targets {
+                    let Ok((_, _, mut trans, _, mut stats, _, mass, powerup)) =
+                        goat_query.get_mut(goat)
+                    else {
+                        continue;
+                    };
+                    let scale = (stats.scale * GROWTH).min(MAX_SCALE);
+                    let growth = scale / stats.scale;
+                    // lifted so the bigger collider doesnt end up in the ground
+                    let lift = player::SHEEP_SIZE.y / 2. * stats.scale * (growth - 1.);
+                    trans.translation.y += lift;
+                    stats.scale = scale;
+                    stats.mass *= growth * growth;
+                    if let Some(mut powerup) = powerup {
+                        powerup.scale_mass(growth * growth);
+                    }
+                    commands.entity(goat).insert(player::goat_body(&stats));
+                    // set after the new body so a chonk power-up keeps multiplying on top of the new weight
+                    let mass = ComputedMass::new(mass.value() * growth * growth);
+                    commands.entity(goat).insert(mass);
+                    for (mut part, child_of, nameplate) in &mut part_query {
+                        if child_of.parent() != goat {
+                            continue;
+                        }
+                        part.translation *= growth;
+                        if !nameplate {
+                            part.scale *= growth;
+                        }
+                    }
+                }
+            }
+            RedeemAction::Shield => {
+                for goat in targets {
+                    let Ok((_, _, _, _, _, mut speed, mut mass, powerup)) =
+                        goat_query.get_mut(goat)
+                    else {
+                        continue;
+                    };
+                    if let Some(powerup) = powerup {
+                        powerup.undo(&mut speed, &mut mass);
+                    }
+                    commands
+                        .entity(goat)
+                        .insert(powerup::ActivePowerUp::shield());
+                }
+            }
+            RedeemAction::Airstrike => {
+                // nobody bombs their own goat by accident
+                let target = match named {
+                    Some(_) => targets.first().copied(),
+                    None => {
+                        let others = goat_query
+                            .iter()
+                            .filter(|(_, player, ..)| !player.is_owned_by(&event.viewer))
+                            .map(|(goat, ..)| goat)
+                            .collect::<Vec<_>>();
+                        (!others.is_empty()).then(|| others[rand::random_range(0..others.len())])
+                    }
+                };
+                let Some(Ok((_, _, trans, linvel, ..))) =
+                    target.map(|target| goat_query.get(target))
+                else {
+                    continue;
+                };
+                // aimed where the goat will be when it lands
+                let lead = linvel.0.with_y(0.) * BOMB_HEIGHT / BOMB_SPEED;
+                commands.spawn(gun::bullet(
+                    &asset_handles,
+                    trans.translation + lead + Vec3::Y * BOMB_HEIGHT,
+                    Vec3::NEG_Y * BOMB_SPEED,
+                ));
+            }
+            RedeemAction::DropGoats(goats) => {
+                let mut taken = goat_query
+                    .iter()
+                    .map(|(_, _, trans, ..)| trans.translation)
+                    .collect::<Vec<_>>();
+                // over the goat cap on purpose, somebody paid for these. theyre bots so
+                // their knockouts dont go to the viewer theyre named after
+                for _ in 0..goats {
+                    join_events.write(player::spawn_bot(
+                        &mut commands,
+                        &asset_handles,
+                        &settings,
+                        &mut players,
+                        &goat_speed,
+                        &planner,
+                        &mut taken,
+                        &format!("{}'s goat", event.viewer),
+                        true,
+                    ));
+                }
+            }
+        }
+
+        feed_events.write(feed::FeedEvent::Redeemed {
+            name: event.viewer.clone(),
+            action: event.action.label(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(reward: Option<&str>, bits: u64) -> twitch::Message {
+        twitch::Message {
+            sender: "someone".to_owned(),
+            display_name: "SomeOne".to_owned(),
+            text: String::new(),
+            badges: vec![],
+            reward: reward.map(|reward| reward.to_owned()),
+            bits,
+        }
+    }
+
+    fn redemptions() -> Redemptions {
+        Redemptions {
+            rewards: vec![Reward {
+                id: "big".to_owned(),
+                action: RedeemAction::BigGoat,
+            }],
+            cheers: vec![
+                Cheer {
+                    bits: 1000,
+                    action: RedeemAction::DropGoats(10),
+                },
+                Cheer {
+                    bits: 100,
+                    action: RedeemAction::Shield,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn rewards_go_by_id() {
+        let redemptions = redemptions();
+        assert_eq!(
+            redemptions.action(&message(Some("big"), 0)),
+            Some(RedeemAction::BigGoat)
+        );
+        assert_eq!(redemptions.action(&message(Some("unknown"), 0)), None);
+        // a reward is never a cheer
+        assert_eq!(redemptions.action(&message(Some("unknown"), 5000)), None);
+    }
+
+    #[test]
+    fn cheers_get_the_biggest_action_they_cover() {
+        let redemptions = redemptions();
+        assert_eq!(redemptions.action(&message(None, 0)), None);
+        assert_eq!(redemptions.action(&message(None, 99)), None);
+        assert_eq!(
+            redemptions.action(&message(None, 100)),
+            Some(RedeemAction::Shield)
+        );
+        assert_eq!(
+            redemptions.action(&message(None, 999)),
+            Some(RedeemAction::Shield)
+        );
+        assert_eq!(
+            redemptions.action(&message(None, 5000)),
+            Some(RedeemAction::DropGoats(10))
+        );
+    }
+}
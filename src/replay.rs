@@ -0,0 +1,369 @@
+use crate::{
+    goat_stats::GoatStats, moderation, pause, player, spectator_camera::CameraMode, team::Team,
+    util, AssetHandles, GameState,
+};
+use avian3d::prelude::*;
+use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+// how much of the past is kept around for replays
+const BUFFER_SECS: f32 = 8.;
+const BEFORE_DEATH_SECS: f32 = 3.;
+const AFTER_DEATH_SECS: f32 = 1.;
+const SLOW_MOTION: f32 = 0.35;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ReplayBuffer::default())
+            .insert_resource(LastElimination::default())
+            .insert_resource(Replay::default())
+            .add_event::<StartReplay>()
+            .add_systems(OnEnter(GameState::Spectating), clear_buffer)
+            .add_systems(
+                FixedUpdate,
+                record_frame.run_if(in_state(GameState::Spectating)),
+            )
+            .add_systems(
+                Update,
+                (
+                    remember_elimination,
+                    replay_on_key.run_if(in_state(GameState::Spectating).and(pause::not_paused)),
+                    begin_replay.run_if(not_replaying),
+                    play_replay.run_if(not(not_replaying)),
+                )
+                    .chain(),
+            )
+            .add_systems(OnEnter(GameState::End), replay_final_hit)
+            .add_systems(OnExit(GameState::End), stop_replay);
+    }
+}
+
+/// plays back the last elimination in slow motion
+#[derive(Event)]
+pub struct StartReplay;
+
+#[derive(Clone, Copy)]
+struct GoatSnapshot {
+    goat: Entity,
+    translation: Vec3,
+    rotation: Quat,
+    velocity: Vec3,
+}
+
+struct Frame {
+    time: f32,
+    goats: Vec<GoatSnapshot>,
+}
+
+#[derive(Resource, Default)]
+struct ReplayBuffer {
+    frames: VecDeque<Frame>,
+    // name, team and size of every goat seen in the buffer
+    names: HashMap<Entity, (String, Option<Team>, f32)>,
+}
+
+#[derive(Resource, Default)]
+struct LastElimination(Option<(Entity, f32)>);
+
+struct Playback {
+    time: f32,
+    end: f32,
+    victim: Entity,
+    // replay entity for every goat in the current frame
+    ghosts: HashMap<Entity, Entity>,
+    focus: Vec3,
+    orbit_angle: f32,
+    saved_camera: Transform,
+    saved_mode: CameraMode,
+    was_paused: bool,
+}
+
+#[derive(Resource, Default)]
+pub struct Replay(Option<Playback>);
+
+impl Replay {
+    pub fn is_playing(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+pub fn not_replaying(replay: Res<Replay>) -> bool {
+    replay.0.is_none()
+}
+
+fn clear_buffer(mut buffer: ResMut<ReplayBuffer>, mut last_elimination: ResMut<LastElimination>) {
+    buffer.frames.clear();
+    buffer.names.clear();
+    last_elimination.0 = None;
+}
+
+fn record_frame(
+    time: Res<Time>,
+    mut buffer: ResMut<ReplayBuffer>,
+    player_query: Query<(
+        Entity,
+        &Transform,
+        &LinearVelocity,
+        &player::Player,
+        &GoatStats,
+        Option<&Team>,
+    )>,
+) {
+    let now = time.elapsed_secs();
+    let mut goats = vec![];
+    for (goat, trans, linvel, player, stats, team) in &player_query {
+        goats.push(GoatSnapshot {
+            goat,
+            translation: trans.translation,
+            rotation: trans.rotation,
+            velocity: linvel.0,
+        });
+        if !buffer.names.contains_key(&goat) {
+            buffer
+                .names
+                .insert(goat, (player.name().to_owned(), team.copied(), stats.scale));
+        }
+    }
+
+    buffer.frames.push_back(Frame { time: now, goats });
+    while buffer
+        .frames
+        .front()
+        .is_some_and(|frame| frame.time < now - BUFFER_SECS)
+    {
+        buffer.frames.pop_front();
+    }
+}
+
+fn remember_elimination(
+    mut death_events: EventReader<player::GoatDied>,
+    buffer: Res<ReplayBuffer>,
+    mut last_elimination: ResMut<LastElimination>,
+) {
+    for event in death_events.read() {
+        let Some(frame) = buffer.frames.back() else {
+            continue;
+        };
+        // by entity since a respawned goat has the same name as its old self
+        if buffer.names.contains_key(&event.entity) {
+            last_elimination.0 = Some((event.entity, frame.time));
+        }
+    }
+}
+
+fn replay_on_key(input: Res<ButtonInput<KeyCode>>, mut replay_events: EventWriter<StartReplay>) {
+    if input.just_pressed(KeyCode::KeyR) {
+        replay_events.write(StartReplay);
+    }
+}
+
+// the hit that ended the round gets a replay behind the end screen
+fn replay_final_hit(mut replay_events: EventWriter<StartReplay>) {
+    replay_events.write(StartReplay);
+}
+
+fn begin_replay(
+    mut replay_events: EventReader<StartReplay>,
+    buffer: Res<ReplayBuffer>,
+    last_elimination: Res<LastElimination>,
+    mut replay: ResMut<Replay>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut camera_mode: ResMut<CameraMode>,
+    camera_query: Single<&Transform, With<Camera>>,
+    mut visibility_query: Query<&mut Visibility, With<player::Player>>,
+) {
+    if replay_events.read().count() == 0 || buffer.frames.len() < 2 {
+        return;
+    }
+    let (Some((victim, death_time)), Some(first), Some(last)) = (
+        last_elimination.0,
+        buffer.frames.front(),
+        buffer.frames.back(),
+    ) else {
+        return;
+    };
+
+    replay.0 = Some(Playback {
+        time: (death_time - BEFORE_DEATH_SECS).max(first.time),
+        end: (death_time + AFTER_DEATH_SECS).min(last.time),
+        victim,
+        ghosts: HashMap::new(),
+        focus: Vec3::ZERO,
+        orbit_angle: 0.,
+        saved_camera: **camera_query,
+        saved_mode: *camera_mode,
+        was_paused: virtual_time.is_paused(),
+    });
+
+    // freeze the real round and hide it while the ghosts play
+    virtual_time.pause();
+    *camera_mode = CameraMode::Replay;
+    for mut visibility in &mut visibility_query {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+// puts the round back the way it was before the replay
+fn end_playback(
+    commands: &mut Commands,
+    playback: &Playback,
+    virtual_time: &mut Time<Virtual>,
+    camera_mode: &mut CameraMode,
+    camera: &mut Transform,
+    visibility_query: &mut Query<&mut Visibility, With<player::Player>>,
+) {
+    for ghost in playback.ghosts.values() {
+        commands.entity(*ghost).despawn();
+    }
+    for mut visibility in visibility_query {
+        *visibility = Visibility::Inherited;
+    }
+    if !playback.was_paused {
+        virtual_time.unpause();
+    }
+    *camera = playback.saved_camera;
+    *camera_mode = playback.saved_mode;
+}
+
+// leaving the end screen before the replay is done shouldnt leave the next round frozen
+fn stop_replay(
+    mut commands: Commands,
+    mut replay: ResMut<Replay>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut camera_mode: ResMut<CameraMode>,
+    mut camera_query: Single<&mut Transform, With<Camera>>,
+    mut visibility_query: Query<&mut Visibility, With<player::Player>>,
+) {
+    if let Some(playback) = replay.0.take() {
+        end_playback(
+            &mut commands,
+            &playback,
+            &mut virtual_time,
+            &mut camera_mode,
+            &mut camera_query,
+            &mut visibility_query,
+        );
+    }
+    virtual_time.unpause();
+}
+
+// smooth position between two snapshots using their velocities
+fn hermite(a: &GoatSnapshot, b: &GoatSnapshot, t: f32, dt: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    a.translation * (2. * t3 - 3. * t2 + 1.)
+        + a.velocity * dt * (t3 - 2. * t2 + t)
+        + b.translation * (-2. * t3 + 3. * t2)
+        + b.velocity * dt * (t3 - t2)
+}
+
+fn play_replay(
+    mut commands: Commands,
+    real_time: Res<Time<Real>>,
+    asset_handles: Res<AssetHandles>,
+    name_filter: Res<moderation::NameFilter>,
+    buffer: Res<ReplayBuffer>,
+    mut replay: ResMut<Replay>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut camera_mode: ResMut<CameraMode>,
+    mut camera_query: Single<&mut Transform, With<Camera>>,
+    mut ghost_query: Query<&mut Transform, Without<Camera>>,
+    mut visibility_query: Query<&mut Visibility, With<player::Player>>,
+) {
+    let Some(playback) = &mut replay.0 else {
+        return;
+    };
+    let dt = real_time.delta_secs();
+    playback.time += dt * SLOW_MOTION;
+
+    if playback.time >= playback.end || buffer.frames.len() < 2 {
+        end_playback(
+            &mut commands,
+            playback,
+            &mut virtual_time,
+            &mut camera_mode,
+            &mut camera_query,
+            &mut visibility_query,
+        );
+        replay.0 = None;
+        return;
+    }
+
+    // frames on both sides of the playback time
+    let next = buffer
+        .frames
+        .partition_point(|frame| frame.time <= playback.time)
+        .clamp(1, buffer.frames.len() - 1);
+    let (frame_a, frame_b) = (&buffer.frames[next - 1], &buffer.frames[next]);
+    let frame_dt = frame_b.time - frame_a.time;
+    let t = ((playback.time - frame_a.time) / frame_dt).clamp(0., 1.);
+    let in_b = frame_b
+        .goats
+        .iter()
+        .map(|snapshot| (snapshot.goat, snapshot))
+        .collect::<HashMap<_, _>>();
+
+    // goats that dropped out of the frame died just now
+    let mut died = vec![];
+    for (goat, ghost) in &playback.ghosts {
+        if !in_b.contains_key(goat) {
+            died.push(*goat);
+            if let Ok(trans) = ghost_query.get(*ghost) {
+                commands.spawn(util::explosion(&asset_handles, trans.translation));
+            }
+            commands.entity(*ghost).despawn();
+        }
+    }
+    for goat in died {
+        playback.ghosts.remove(&goat);
+    }
+
+    for a in &frame_a.goats {
+        let Some(b) = in_b.get(&a.goat) else {
+            continue;
+        };
+        let scale = buffer.names.get(&a.goat).map_or(1., |(_, _, scale)| *scale);
+        // ghosts have no collider so the whole thing is scaled, nameplate too
+        let trans = Transform::from_translation(hermite(a, b, t, frame_dt))
+            .with_rotation(a.rotation.slerp(b.rotation, t))
+            .with_scale(Vec3::splat(scale));
+        if a.goat == playback.victim {
+            playback.focus = trans.translation;
+        }
+
+        match playback.ghosts.get(&a.goat) {
+            Some(ghost) => {
+                if let Ok(mut ghost_trans) = ghost_query.get_mut(*ghost) {
+                    *ghost_trans = trans;
+                }
+            }
+            None => {
+                let (name, team, _) =
+                    buffer
+                        .names
+                        .get(&a.goat)
+                        .cloned()
+                        .unwrap_or((String::new(), None, 1.));
+                let ghost = commands
+                    .spawn(player::ghost_goat(
+                        &asset_handles,
+                        name_filter.display(&name),
+                        team,
+                        trans,
+                    ))
+                    .id();
+                playback.ghosts.insert(a.goat, ghost);
+            }
+        }
+    }
+
+    // slow circle around the goat that gets taken out
+    playback.orbit_angle += 0.4 * dt;
+    let angle = playback.orbit_angle;
+    **camera_query = Transform::from_translation(
+        playback.focus + vec3(angle.cos() * 30., 12., angle.sin() * 30.),
+    )
+    .looking_at(playback.focus, Vec3::Y);
+}
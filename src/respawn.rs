@@ -0,0 +1,173 @@
+use crate::{menu, moderation, player, spawn::SpawnPlanner, AssetHandles, GameState};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub struct RespawnPlugin;
+
+impl Plugin for RespawnPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Respawns::default())
+            .add_systems(OnEnter(GameState::Spectating), clear_respawns)
+            .add_systems(
+                Update,
+                (queue_respawns, respawn_goats)
+                    .chain()
+                    .run_if(in_state(GameState::Spectating)),
+            );
+    }
+}
+
+/// whether dead goats come back, rounds with respawns are scored by knockouts
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum RespawnMode {
+    // dead is dead
+    #[default]
+    Off,
+    // goats keep coming back until the round is ended by hand or the mode is won
+    Endless,
+    // goats keep coming back until the clock runs out
+    Timed,
+}
+
+impl RespawnMode {
+    pub fn next(self) -> Self {
+        match self {
+            RespawnMode::Off => RespawnMode::Endless,
+            RespawnMode::Endless => RespawnMode::Timed,
+            RespawnMode::Timed => RespawnMode::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RespawnMode::Off => "off",
+            RespawnMode::Endless => "endless",
+            RespawnMode::Timed => "timed",
+        }
+    }
+}
+
+// dead goats and how many seconds until theyre back
+#[derive(Resource, Default)]
+struct Respawns(Vec<(player::RosterEntry, f32)>);
+
+impl Respawns {
+    // counts everyone down and hands back the goats whose time is up
+    fn tick(&mut self, dt: f32) -> Vec<player::RosterEntry> {
+        for (_, secs_left) in &mut self.0 {
+            *secs_left -= dt;
+        }
+        let (ready, waiting) = std::mem::take(&mut self.0)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, secs_left)| *secs_left <= 0.);
+        self.0 = waiting;
+        ready.into_iter().map(|(goat, _)| goat).collect()
+    }
+
+    fn forget(&mut self, viewer: &str) {
+        self.0
+            .retain(|(goat, _)| !player::is_owned_by(&goat.name, viewer));
+    }
+}
+
+fn clear_respawns(mut respawns: ResMut<Respawns>) {
+    respawns.0.clear();
+}
+
+fn queue_respawns(
+    settings: Res<menu::Settings>,
+    mut respawns: ResMut<Respawns>,
+    mut death_events: EventReader<player::GoatDied>,
+    mut mod_events: EventReader<moderation::ModAction>,
+) {
+    for event in death_events.read() {
+        if settings.respawn != RespawnMode::Off {
+            respawns.0.push((event.goat.clone(), settings.respawn_secs));
+        }
+    }
+    // kicked while dead means staying dead
+    for action in mod_events.read() {
+        if let moderation::ModCommand::Kick(viewer) | moderation::ModCommand::Ban(viewer) =
+            &action.command
+        {
+            respawns.forget(viewer);
+        }
+    }
+}
+
+fn respawn_goats(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_handles: Res<AssetHandles>,
+    mut players: ResMut<player::Players>,
+    mut respawns: ResMut<Respawns>,
+    planner: SpawnPlanner,
+    goat_query: Query<&Transform, With<player::Player>>,
+) {
+    let mut taken = goat_query
+        .iter()
+        .map(|trans| trans.translation)
+        .collect::<Vec<_>>();
+    for goat in respawns.tick(time.delta_secs()) {
+        // already back from joining again
+        if players.0.contains(&goat.name) {
+            continue;
+        }
+        let pos = planner.spot(&mut taken, goat.team);
+        players.0.insert(goat.name.clone());
+        player::spawn_entry(
+            &mut commands,
+            &asset_handles,
+            &planner,
+            &goat,
+            vec3(pos.x, player::DROP_HEIGHT, pos.z),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dead_goat(name: &str) -> player::RosterEntry {
+        player::RosterEntry {
+            name: name.to_owned(),
+            team: None,
+            stats: Default::default(),
+            bot: false,
+        }
+    }
+
+    fn names(goats: &[player::RosterEntry]) -> Vec<&str> {
+        goats.iter().map(|goat| goat.name.as_str()).collect()
+    }
+
+    #[test]
+    fn goats_come_back_when_their_time_is_up() {
+        let mut respawns = Respawns(vec![(dead_goat("a"), 5.), (dead_goat("b"), 2.)]);
+        assert!(respawns.tick(1.).is_empty());
+        assert_eq!(names(&respawns.tick(1.)), ["b"]);
+        assert_eq!(respawns.0.len(), 1);
+        assert!(respawns.tick(2.5).is_empty());
+        assert_eq!(names(&respawns.tick(0.5)), ["a"]);
+        assert!(respawns.0.is_empty());
+    }
+
+    #[test]
+    fn long_frames_bring_everyone_back_at_once() {
+        let mut respawns = Respawns(vec![(dead_goat("a"), 5.), (dead_goat("b"), 2.)]);
+        assert_eq!(names(&respawns.tick(10.)), ["a", "b"]);
+        assert!(respawns.tick(10.).is_empty());
+    }
+
+    #[test]
+    fn kicked_goats_stay_dead() {
+        let mut respawns = Respawns(vec![
+            (dead_goat("someone"), 1.),
+            (dead_goat("someone 2"), 1.),
+            (dead_goat("someoneelse"), 1.),
+        ]);
+        respawns.forget("SomeOne");
+        assert_eq!(names(&respawns.tick(1.)), ["someoneelse"]);
+    }
+}
@@ -0,0 +1,287 @@
+use crate::{attribution, game_mode, gun, moderation, player, GameState};
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+// goats listed on the board
+const TOP_GOATS: usize = 5;
+// the layout is made for this window height and scaled from there
+const BASE_HEIGHT: f32 = 1080.;
+
+pub struct ScoreboardPlugin;
+
+impl Plugin for ScoreboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RoundStats::default())
+            .insert_resource(ScoreboardVisible(true))
+            .add_systems(
+                OnEnter(GameState::Spectating),
+                (reset_stats, setup_scoreboard),
+            )
+            .add_systems(
+                Update,
+                (
+                    track_damage,
+                    count_knockouts,
+                    toggle_scoreboard,
+                    show_scoreboard.run_if(resource_changed::<ScoreboardVisible>),
+                    update_scoreboard,
+                    scale_scoreboard,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Spectating)),
+            )
+            .add_systems(OnExit(GameState::Spectating), despawn_scoreboard);
+    }
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct GoatStats {
+    pub knockouts: u32,
+    // roughly how much speed this goat knocked into others
+    pub damage: f32,
+}
+
+/// per goat numbers for the current round
+#[derive(Resource, Default)]
+pub struct RoundStats {
+    pub goats: HashMap<String, GoatStats>,
+    // every goat that was in the round at some point
+    pub seen: HashSet<String>,
+}
+
+impl RoundStats {
+    pub fn top(&self, count: usize) -> Vec<(&String, &GoatStats)> {
+        let mut goats = self.goats.iter().collect::<Vec<_>>();
+        goats.sort_by(|a, b| {
+            b.1.knockouts
+                .cmp(&a.1.knockouts)
+                .then(b.1.damage.total_cmp(&a.1.damage))
+        });
+        goats.truncate(count);
+        goats
+    }
+}
+
+#[derive(Resource)]
+pub struct ScoreboardVisible(pub bool);
+
+#[derive(Component)]
+struct Scoreboard;
+
+#[derive(Component)]
+struct AliveText;
+
+#[derive(Component)]
+struct TopGoatsText;
+
+#[derive(Component)]
+struct AmmoText;
+
+// font size at BASE_HEIGHT
+#[derive(Component)]
+struct BaseFontSize(f32);
+
+fn reset_stats(mut stats: ResMut<RoundStats>) {
+    stats.goats.clear();
+    stats.seen.clear();
+}
+
+fn track_damage(mut hit_events: EventReader<attribution::Hit>, mut stats: ResMut<RoundStats>) {
+    for hit in hit_events.read() {
+        if !hit.counts() {
+            continue;
+        }
+        if let attribution::Attacker::Goat { name, .. } = &hit.attacker {
+            stats.goats.entry(name.clone()).or_default().damage += hit.strength;
+        }
+    }
+}
+
+fn count_knockouts(
+    mut death_events: EventReader<player::GoatDied>,
+    mut stats: ResMut<RoundStats>,
+    player_query: Query<&player::Player>,
+) {
+    for player in &player_query {
+        if !stats.seen.contains(player.name()) {
+            stats.seen.insert(player.name().to_owned());
+        }
+    }
+    for event in death_events.read() {
+        if let Some(killer) = &event.killer {
+            stats.goats.entry(killer.clone()).or_default().knockouts += 1;
+        }
+    }
+}
+
+fn hud_text(text: &str, font_size: f32, marker: impl Component) -> impl Bundle {
+    (
+        marker,
+        Text::new(text),
+        TextFont::default().with_font_size(font_size),
+        BaseFontSize(font_size),
+    )
+}
+
+fn setup_scoreboard(mut commands: Commands, visible: Res<ScoreboardVisible>) {
+    let visibility = if visible.0 {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+
+    commands.spawn((
+        Scoreboard,
+        visibility,
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.),
+            top: Val::Percent(2.),
+            justify_content: JustifyContent::Center,
+            ..Default::default()
+        },
+        children![hud_text("", 34., AliveText)],
+    ));
+    commands.spawn((
+        Scoreboard,
+        visibility,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(20.),
+            left: Val::Percent(1.),
+            padding: UiRect::all(Val::Percent(0.5)),
+            ..Default::default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.4)),
+        BorderRadius::all(Val::Px(8.)),
+        children![hud_text("", 22., TopGoatsText)],
+    ));
+    commands.spawn((
+        Scoreboard,
+        visibility,
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Percent(2.),
+            right: Val::Percent(1.),
+            ..Default::default()
+        },
+        children![hud_text("", 28., AmmoText)],
+    ));
+}
+
+fn toggle_scoreboard(input: Res<ButtonInput<KeyCode>>, mut visible: ResMut<ScoreboardVisible>) {
+    if input.just_pressed(KeyCode::KeyH) {
+        visible.0 = !visible.0;
+    }
+}
+
+// the pause menu can hide it too
+fn show_scoreboard(
+    visible: Res<ScoreboardVisible>,
+    mut scoreboard_query: Query<&mut Visibility, With<Scoreboard>>,
+) {
+    for mut visibility in &mut scoreboard_query {
+        *visibility = if visible.0 {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+fn update_scoreboard(
+    stats: Res<RoundStats>,
+    round_time: Res<game_mode::RoundTime>,
+    gun: Res<gun::Gun>,
+    name_filter: Res<moderation::NameFilter>,
+    player_query: Query<(), With<player::Player>>,
+    mut alive_text: Single<&mut Text, (With<AliveText>, Without<TopGoatsText>, Without<AmmoText>)>,
+    mut top_text: Single<&mut Text, (With<TopGoatsText>, Without<AmmoText>)>,
+    mut ammo_text: Single<&mut Text, With<AmmoText>>,
+) {
+    let secs = round_time.0 as u32;
+    alive_text.0 = format!(
+        "{} / {} alive    {:02}:{:02}",
+        player_query.iter().count(),
+        stats.seen.len(),
+        secs / 60,
+        secs % 60
+    );
+
+    let mut lines = vec!["TOP GOATS".to_owned()];
+    for (i, (name, goat)) in stats.top(TOP_GOATS).into_iter().enumerate() {
+        lines.push(format!(
+            "{}. {}  {} ko  {} dmg",
+            i + 1,
+            name_filter.display(name),
+            goat.knockouts,
+            goat.damage as u32
+        ));
+    }
+    top_text.0 = lines.join("\n");
+
+    ammo_text.0 = format!("{}  unlimited ammo", gun.weapon);
+}
+
+// keeps the hud the same size relative to the window
+fn scale_scoreboard(
+    window: Single<&Window>,
+    mut text_query: Query<(&mut TextFont, &BaseFontSize)>,
+) {
+    let scale = window.height() / BASE_HEIGHT;
+    for (mut font, base) in &mut text_query {
+        let size = base.0 * scale;
+        if font.font_size != size {
+            font.font_size = size;
+        }
+    }
+}
+
+fn despawn_scoreboard(mut commands: Commands, scoreboard_query: Query<Entity, With<Scoreboard>>) {
+    for scoreboard in &scoreboard_query {
+        commands.entity(scoreboard).despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(goats: &[(&str, u32, f32)]) -> RoundStats {
+        RoundStats {
+            goats: goats
+                .iter()
+                .map(|(name, knockouts, damage)| {
+                    let stats = GoatStats {
+                        knockouts: *knockouts,
+                        damage: *damage,
+                    };
+                    ((*name).to_owned(), stats)
+                })
+                .collect(),
+            seen: HashSet::new(),
+        }
+    }
+
+    #[test]
+    fn most_knockouts_come_first() {
+        let stats = stats(&[("a", 1, 500.), ("b", 3, 10.), ("c", 2, 0.)]);
+        let top = stats.top(TOP_GOATS);
+        let names = top
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["b", "c", "a"]);
+    }
+
+    #[test]
+    fn damage_breaks_knockout_ties() {
+        let stats = stats(&[("a", 2, 10.), ("b", 2, 80.), ("c", 0, 200.)]);
+        let top = stats.top(2);
+        let names = top
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["b", "a"]);
+    }
+}
@@ -0,0 +1,421 @@
+use crate::{menu, player, team::Team, GameState};
+use avian3d::prelude::*;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// half the size of the field, the fence is at +-300
+const FIELD: f32 = 300.;
+// random spots tried before settling for the best one so far
+const TRIES: usize = 30;
+// the ring never gets so small that everyone starts in the middle
+const MIN_RING_RADIUS: f32 = 80.;
+// how much of the field each team corner gets
+const CORNER_SIZE: f32 = 0.4;
+
+pub struct SpawnPlugin;
+
+impl Plugin for SpawnPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SpawnZones(default_zones()))
+            .insert_resource(Arranged(true))
+            .add_systems(OnEnter(GameState::Spectating), start_arranging)
+            // hazards spawn with the round so they only show up in spatial queries after a physics step
+            .add_systems(
+                FixedPostUpdate,
+                arrange_goats
+                    .after(PhysicsSet::Sync)
+                    .run_if(in_state(GameState::Spectating).and(resource_equals(Arranged(false)))),
+            );
+    }
+}
+
+/// where goats start the round
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum SpawnLayout {
+    // anywhere, but never too close to each other
+    #[default]
+    Scatter,
+    // evenly around a circle
+    Ring,
+    // lined up from the middle out
+    Grid,
+    // every team in its own corner
+    TeamCorners,
+    // only inside the spawn zones of the map
+    Zones,
+}
+
+impl SpawnLayout {
+    pub fn next(self) -> Self {
+        match self {
+            SpawnLayout::Scatter => SpawnLayout::Ring,
+            SpawnLayout::Ring => SpawnLayout::Grid,
+            SpawnLayout::Grid => SpawnLayout::TeamCorners,
+            SpawnLayout::TeamCorners => SpawnLayout::Zones,
+            SpawnLayout::Zones => SpawnLayout::Scatter,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SpawnLayout::Scatter => "scatter",
+            SpawnLayout::Ring => "ring",
+            SpawnLayout::Grid => "grid",
+            SpawnLayout::TeamCorners => "team corners",
+            SpawnLayout::Zones => "map zones",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum SpawnFacing {
+    #[default]
+    Center,
+    Random,
+}
+
+/// a circle goats can spawn in
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct SpawnZone {
+    pub x: f32,
+    pub z: f32,
+    pub radius: f32,
+}
+
+/// the spawn zones of the map, used by the zones layout
+#[derive(Resource, Clone)]
+pub struct SpawnZones(pub Vec<SpawnZone>);
+
+/// the zones used when theres no map to take them from, between the default hazards
+pub fn default_zones() -> Vec<SpawnZone> {
+    [(1., 0.), (-1., 0.), (0., 1.), (0., -1.)]
+        .into_iter()
+        .map(|(x, z)| SpawnZone {
+            x: x * 110.,
+            z: z * 110.,
+            radius: 35.,
+        })
+        .collect()
+}
+
+// false until the goats were put in their start spots this round
+#[derive(Resource, PartialEq, Eq)]
+struct Arranged(bool);
+
+/// picks spots for new goats, everything that spawns a goat goes through this
+#[derive(SystemParam)]
+pub struct SpawnPlanner<'w, 's> {
+    settings: Res<'w, menu::Settings>,
+    zones: Res<'w, SpawnZones>,
+    spatial_query: SpatialQuery<'w, 's>,
+}
+
+impl SpawnPlanner<'_, '_> {
+    /// a free spot on the ground for one more goat, taken is where goats are or are about to be
+    pub fn spot(&self, taken: &mut Vec<Vec3>, team: Option<Team>) -> Vec3 {
+        self.spot_excluding(taken, team, &[])
+    }
+
+    /// the rotation a goat spawned at pos should start with
+    pub fn facing(&self, pos: Vec3) -> f32 {
+        match self.settings.spawn_facing {
+            // the forward of a goat is -z
+            SpawnFacing::Center => pos.x.atan2(pos.z),
+            SpawnFacing::Random => rand::random_range(0.0..std::f32::consts::TAU),
+        }
+    }
+
+    // poisson disk by throwing darts, the first spot far enough from everyone wins
+    // and if theres none the one furthest from the others is used
+    fn spot_excluding(&self, taken: &mut Vec<Vec3>, team: Option<Team>, goats: &[Entity]) -> Vec3 {
+        let spacing = self.settings.spawn_spacing;
+        let mut best = None;
+        for _ in 0..TRIES {
+            let pos = self.candidate(team, taken.len());
+            if !self.is_clear(pos, goats) {
+                continue;
+            }
+            let free = free_space(taken, pos);
+            if free >= spacing {
+                best = Some(pos);
+                break;
+            }
+            if best.is_none_or(|best| free > free_space(taken, best)) {
+                best = Some(pos);
+            }
+        }
+        // the whole area is blocked, better a bad spot than none
+        let pos = best.unwrap_or_else(|| self.candidate(team, taken.len()));
+        taken.push(pos);
+        pos
+    }
+
+    fn candidate(&self, team: Option<Team>, index: usize) -> Vec3 {
+        candidate(&self.settings, &self.zones.0, team, index)
+    }
+
+    fn ring_radius(&self, goats: usize) -> f32 {
+        ring_radius(&self.settings, goats)
+    }
+
+    // nothing but the ground where a goat would stand, pits and lava count too
+    fn is_clear(&self, pos: Vec3, goats: &[Entity]) -> bool {
+        let size = player::SHEEP_SIZE * 2.;
+        // from just over the ground up to well over a goat
+        let probe = Collider::cuboid(size.x, 9., size.z);
+        self.spatial_query
+            .shape_intersections(
+                &probe,
+                vec3(pos.x, 5.5, pos.z),
+                Quat::IDENTITY,
+                &SpatialQueryFilter::default().with_excluded_entities(goats.iter().copied()),
+            )
+            .is_empty()
+    }
+
+    /// start spots for a whole round of goats at once, in the same order
+    fn arrange(&self, goats: &[(Entity, Option<Team>)]) -> Vec<Vec3> {
+        let entities = goats.iter().map(|(goat, _)| *goat).collect::<Vec<_>>();
+        let mut taken = vec![];
+        match self.settings.spawn_layout {
+            SpawnLayout::Ring => {
+                let radius = self.ring_radius(goats.len());
+                let step = std::f32::consts::TAU / goats.len().max(1) as f32;
+                for i in 0..goats.len() {
+                    let on_ring = Vec2::from_angle(step * i as f32) * radius;
+                    let pos = vec3(on_ring.x, 3., on_ring.y);
+                    if self.is_clear(pos, &entities) {
+                        taken.push(pos);
+                    } else {
+                        self.spot_excluding(&mut taken, None, &entities);
+                    }
+                }
+            }
+            SpawnLayout::Grid => {
+                // every cell sorted from the middle out, blocked ones are skipped
+                let pitch = self.settings.spawn_spacing;
+                let cells = ((FIELD - self.settings.spawn_margin) / pitch) as i32;
+                let mut spots = (-cells..=cells)
+                    .flat_map(|x| (-cells..=cells).map(move |z| vec3(x as f32, 0., z as f32)))
+                    .map(|cell| cell * pitch + Vec3::Y * 3.)
+                    .collect::<Vec<_>>();
+                spots.sort_by(|a, b| a.length_squared().total_cmp(&b.length_squared()));
+                let mut spots = spots
+                    .into_iter()
+                    .filter(|pos| self.is_clear(*pos, &entities));
+                for _ in goats {
+                    match spots.next() {
+                        Some(pos) => taken.push(pos),
+                        None => {
+                            self.spot_excluding(&mut taken, None, &entities);
+                        }
+                    }
+                }
+            }
+            _ => {
+                for (_, team) in goats {
+                    self.spot_excluding(&mut taken, *team, &entities);
+                }
+            }
+        }
+        taken
+    }
+}
+
+// a random spot in the area the layout allows
+fn candidate(
+    settings: &menu::Settings,
+    zones: &[SpawnZone],
+    team: Option<Team>,
+    index: usize,
+) -> Vec3 {
+    let edge = FIELD - settings.spawn_margin;
+    let in_square = |center: Vec2, half: f32| {
+        let min = (center - half).max(Vec2::splat(-edge));
+        let max = (center + half).min(Vec2::splat(edge));
+        vec2(
+            rand::random_range(min.x..=max.x),
+            rand::random_range(min.y..=max.y),
+        )
+    };
+    let in_circle = |center: Vec2, radius: f32| {
+        let angle = rand::random_range(0.0..std::f32::consts::TAU);
+        // sqrt so the middle isnt more crowded than the edge
+        let dist = radius * rand::random::<f32>().sqrt();
+        (center + Vec2::from_angle(angle) * dist).clamp(Vec2::splat(-edge), Vec2::splat(edge))
+    };
+
+    let pos = match (settings.spawn_layout, team) {
+        (SpawnLayout::Ring, _) => {
+            let angle = rand::random_range(0.0..std::f32::consts::TAU);
+            Vec2::from_angle(angle) * ring_radius(settings, index + 1)
+        }
+        (SpawnLayout::Grid, _) => {
+            // snapped to the grid the round starts on, which stops at the last whole cell
+            let pitch = settings.spawn_spacing;
+            let cells = Vec2::splat((edge / pitch).floor());
+            (in_square(Vec2::ZERO, edge) / pitch)
+                .round()
+                .clamp(-cells, cells)
+                * pitch
+        }
+        (SpawnLayout::TeamCorners, Some(team)) => {
+            let half = edge * CORNER_SIZE;
+            in_square(corner(team) * (edge - half), half)
+        }
+        (SpawnLayout::Zones, _) if !zones.is_empty() => {
+            let zone = zones[rand::random_range(0..zones.len())];
+            in_circle(vec2(zone.x, zone.z), zone.radius)
+        }
+        _ => in_square(Vec2::ZERO, edge),
+    };
+    vec3(pos.x, 3., pos.y)
+}
+
+fn ring_radius(settings: &menu::Settings, goats: usize) -> f32 {
+    let edge = FIELD - settings.spawn_margin;
+    (goats as f32 * settings.spawn_spacing / std::f32::consts::TAU)
+        .clamp(MIN_RING_RADIUS.min(edge), edge)
+}
+
+// how far pos is from the closest goat, on the ground
+fn free_space(taken: &[Vec3], pos: Vec3) -> f32 {
+    taken
+        .iter()
+        .map(|other| other.xz().distance(pos.xz()))
+        .fold(f32::MAX, f32::min)
+}
+
+// which way from the middle a teams corner is
+fn corner(team: Team) -> Vec2 {
+    match team {
+        Team::Red => vec2(-1., -1.),
+        Team::Blue => vec2(1., 1.),
+        Team::Green => vec2(1., -1.),
+        Team::Yellow => vec2(-1., 1.),
+    }
+}
+
+fn start_arranging(mut arranged: ResMut<Arranged>) {
+    arranged.0 = false;
+}
+
+fn arrange_goats(
+    planner: SpawnPlanner,
+    mut arranged: ResMut<Arranged>,
+    mut goat_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut LinearVelocity,
+            &mut AngularVelocity,
+            Option<&Team>,
+        ),
+        With<player::Player>,
+    >,
+) {
+    arranged.0 = true;
+    let goats = goat_query
+        .iter()
+        .map(|(goat, _, _, _, team)| (goat, team.copied()))
+        .collect::<Vec<_>>();
+    let spots = planner.arrange(&goats);
+    for ((goat, _), pos) in goats.iter().zip(spots) {
+        let Ok((_, mut trans, mut linvel, mut angvel, _)) = goat_query.get_mut(*goat) else {
+            continue;
+        };
+        *trans = Transform::from_translation(pos)
+            .with_rotation(Quat::from_rotation_y(planner.facing(pos)));
+        linvel.0 = Vec3::ZERO;
+        angvel.0 = Vec3::ZERO;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(spawn_layout: SpawnLayout) -> menu::Settings {
+        menu::Settings {
+            spawn_layout,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn spots_stay_away_from_the_fence() {
+        let zones = default_zones();
+        for layout in [
+            SpawnLayout::Scatter,
+            SpawnLayout::Ring,
+            SpawnLayout::Grid,
+            SpawnLayout::TeamCorners,
+            SpawnLayout::Zones,
+        ] {
+            let settings = settings(layout);
+            let edge = FIELD - settings.spawn_margin;
+            for i in 0..500 {
+                let team = Team::ALL.get(i % 5).copied();
+                let pos = candidate(&settings, &zones, team, i);
+                assert!(
+                    pos.xz().abs().max_element() <= edge + 0.01,
+                    "{layout:?} put a goat at {pos}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn teams_start_in_their_own_corner() {
+        let settings = settings(SpawnLayout::TeamCorners);
+        for team in Team::ALL {
+            for i in 0..100 {
+                let pos = candidate(&settings, &[], Some(team), i);
+                assert_eq!(pos.xz().signum(), corner(team), "{team:?} at {pos}");
+            }
+        }
+    }
+
+    #[test]
+    fn zones_only_spawn_inside_a_zone() {
+        let settings = settings(SpawnLayout::Zones);
+        let zones = default_zones();
+        for i in 0..200 {
+            let pos = candidate(&settings, &zones, None, i);
+            assert!(zones
+                .iter()
+                .any(|zone| pos.xz().distance(vec2(zone.x, zone.z)) <= zone.radius + 0.01));
+        }
+    }
+
+    #[test]
+    fn grid_spots_are_on_the_grid() {
+        let settings = settings(SpawnLayout::Grid);
+        let pitch = settings.spawn_spacing;
+        for i in 0..200 {
+            let cell = candidate(&settings, &[], None, i).xz() / pitch;
+            assert!(cell.abs_diff_eq(cell.round(), 0.001));
+        }
+    }
+
+    #[test]
+    fn ring_grows_with_the_goats() {
+        let settings = settings(SpawnLayout::Ring);
+        let edge = FIELD - settings.spawn_margin;
+        assert_eq!(ring_radius(&settings, 1), MIN_RING_RADIUS);
+        assert_eq!(ring_radius(&settings, 1000), edge);
+        assert!(ring_radius(&settings, 40) > ring_radius(&settings, 30));
+
+        let pos = candidate(&settings, &[], None, 39);
+        assert!((pos.xz().length() - ring_radius(&settings, 40)).abs() < 0.01);
+    }
+
+    #[test]
+    fn free_space_is_measured_on_the_ground() {
+        assert_eq!(free_space(&[], Vec3::ZERO), f32::MAX);
+        let taken = [vec3(10., 50., 0.), vec3(0., 0., 30.)];
+        assert_eq!(free_space(&taken, Vec3::ZERO), 10.);
+        assert_eq!(free_space(&taken, vec3(0., 0., 25.)), 5.);
+    }
+}
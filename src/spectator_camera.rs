@@ -1,64 +1,247 @@
-use crate::GameState;
+use crate::{follow_camera, pause, GameState};
+use bevy::input::mouse::{AccumulatedMouseMotion, AccumulatedMouseScroll, MouseScrollUnit};
 use bevy::prelude::*;
 
+pub const MIN_SPEED: f32 = 10.;
+pub const MAX_SPEED: f32 = 1000.;
+// a bit less than straight up or down so the camera cant flip over
+const MAX_PITCH: f32 = 1.54;
+
 pub struct SpectatorCameraPlugin;
 
 impl Plugin for SpectatorCameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            FixedUpdate,
-            (move_camera, rotate_camera).run_if(in_state(GameState::Spectating)),
-        );
+        app.insert_resource(CameraMode::default())
+            .insert_resource(CameraBindings::default())
+            .insert_resource(FreeFly::default())
+            .add_systems(
+                Update,
+                (
+                    toggle_camera_mode
+                        .run_if(in_state(GameState::Spectating).and(follow_camera::not_searching)),
+                    // recorded rounds are watched with the free camera too
+                    (change_speed, move_camera, rotate_camera)
+                        .run_if(resource_equals(CameraMode::Manual).and(
+                            in_state(GameState::Spectating).or(in_state(GameState::Playback)),
+                        )),
+                )
+                    .run_if(pause::not_paused),
+            );
     }
 }
 
-fn move_camera(
-    mut transform_query: Query<&mut Transform, With<Camera>>,
-    input: Res<ButtonInput<KeyCode>>,
-) {
-    let camera_speed = if input.pressed(KeyCode::ShiftLeft) {
-        3.
-    } else {
-        1.5
-    };
-    let mut tf = transform_query.single_mut().unwrap();
+#[derive(Resource, Default, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum CameraMode {
+    #[default]
+    Manual,
+    // the director picks the shots
+    Auto,
+    // locked onto one goat
+    Follow,
+    // an instant replay has the camera
+    Replay,
+}
 
-    let forw = tf.clone().forward().normalize();
-    let right = tf.clone().right().normalize();
-    let up = tf.clone().up().normalize();
+/// keys and buttons for the free camera
+#[derive(Resource, Clone, Debug)]
+pub struct CameraBindings {
+    pub forward: KeyCode,
+    pub back: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub down: KeyCode,
+    pub up: KeyCode,
+    pub boost: KeyCode,
+    pub toggle_mode: KeyCode,
+    // hold to stop looking around with the mouse
+    pub freeze_look: MouseButton,
+    pub mouse_sensitivity: f32,
+    // radians per second at full stick
+    pub gamepad_look_speed: f32,
+}
 
-    if input.pressed(KeyCode::KeyW) {
-        tf.translation += forw * camera_speed;
+impl Default for CameraBindings {
+    fn default() -> Self {
+        CameraBindings {
+            forward: KeyCode::KeyW,
+            back: KeyCode::KeyS,
+            left: KeyCode::KeyA,
+            right: KeyCode::KeyD,
+            down: KeyCode::KeyQ,
+            up: KeyCode::KeyE,
+            boost: KeyCode::ShiftLeft,
+            toggle_mode: KeyCode::KeyC,
+            freeze_look: MouseButton::Right,
+            mouse_sensitivity: 8e-3,
+            gamepad_look_speed: 2.5,
+        }
     }
-    if input.pressed(KeyCode::KeyS) {
-        tf.translation -= forw * camera_speed;
+}
+
+#[derive(Resource)]
+pub struct FreeFly {
+    velocity: Vec3,
+    // top speed without boost, changed with the scroll wheel
+    pub speed: f32,
+    pub acceleration: f32,
+    pub damping: f32,
+}
+
+impl Default for FreeFly {
+    fn default() -> Self {
+        FreeFly {
+            velocity: Vec3::ZERO,
+            speed: 96.,
+            acceleration: 8.,
+            damping: 4.,
+        }
     }
-    if input.pressed(KeyCode::KeyA) {
-        tf.translation -= right * camera_speed;
+}
+
+impl FreeFly {
+    // how far the camera moves this frame, the same over a second at any frame rate
+    fn step(&mut self, dir: Vec3, boost: bool, dt: f32) -> Vec3 {
+        let top_speed = if boost { self.speed * 2. } else { self.speed };
+        let wanted = dir.clamp_length_max(1.) * top_speed;
+        // speed up towards the input, slow down on its own when theres none
+        let rate = if dir == Vec3::ZERO {
+            self.damping
+        } else {
+            self.acceleration
+        };
+        self.velocity = self.velocity.lerp(wanted, 1. - (-rate * dt).exp());
+        self.velocity * dt
     }
-    if input.pressed(KeyCode::KeyD) {
-        tf.translation += right * camera_speed;
+}
+
+fn toggle_camera_mode(
+    mut camera_mode: ResMut<CameraMode>,
+    bindings: Res<CameraBindings>,
+    input: Res<ButtonInput<KeyCode>>,
+) {
+    if !input.just_pressed(bindings.toggle_mode) {
+        return;
     }
-    if input.pressed(KeyCode::KeyQ) {
-        tf.translation -= up * camera_speed;
+
+    *camera_mode = match *camera_mode {
+        CameraMode::Manual => CameraMode::Auto,
+        CameraMode::Auto | CameraMode::Follow => CameraMode::Manual,
+        CameraMode::Replay => return,
+    };
+}
+
+fn change_speed(scroll: Res<AccumulatedMouseScroll>, mut free_fly: ResMut<FreeFly>) {
+    let steps = match scroll.unit {
+        MouseScrollUnit::Line => scroll.delta.y,
+        MouseScrollUnit::Pixel => scroll.delta.y / 100.,
+    };
+    free_fly.speed = (free_fly.speed * 1.1_f32.powf(steps)).clamp(MIN_SPEED, MAX_SPEED);
+}
+
+fn move_camera(
+    time: Res<Time>,
+    mut free_fly: ResMut<FreeFly>,
+    bindings: Res<CameraBindings>,
+    input: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut transform_query: Single<&mut Transform, With<Camera>>,
+) {
+    let tf = &mut **transform_query;
+    let forw = tf.forward().normalize();
+    let right = tf.right().normalize();
+    let up = tf.up().normalize();
+
+    let mut dir = Vec3::ZERO;
+    let mut boost = input.pressed(bindings.boost);
+    for (key, key_dir) in [
+        (bindings.forward, forw),
+        (bindings.back, -forw),
+        (bindings.left, -right),
+        (bindings.right, right),
+        (bindings.down, -up),
+        (bindings.up, up),
+    ] {
+        if input.pressed(key) {
+            dir += key_dir;
+        }
     }
-    if input.pressed(KeyCode::KeyE) {
-        tf.translation += up * camera_speed;
+    for gamepad in &gamepads {
+        let stick = gamepad.left_stick();
+        dir += forw * stick.y + right * stick.x;
+        if gamepad.pressed(GamepadButton::RightTrigger2) {
+            dir += up;
+        }
+        if gamepad.pressed(GamepadButton::LeftTrigger2) {
+            dir -= up;
+        }
+        boost |= gamepad.pressed(GamepadButton::South);
     }
+
+    tf.translation += free_fly.step(dir, boost, time.delta_secs());
 }
 
 fn rotate_camera(
-    mut transform_query: Query<&mut Transform, With<Camera>>,
-    mouse_motion: Res<bevy::input::mouse::AccumulatedMouseMotion>,
+    time: Res<Time>,
+    bindings: Res<CameraBindings>,
+    mouse_motion: Res<AccumulatedMouseMotion>,
     mouse_button_input: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    mut transform_query: Single<&mut Transform, With<Camera>>,
 ) {
-    if mouse_button_input.pressed(MouseButton::Right) {
+    let mut look = Vec2::ZERO;
+    if !mouse_button_input.pressed(bindings.freeze_look) {
+        look += mouse_motion.delta * bindings.mouse_sensitivity;
+    }
+    for gamepad in &gamepads {
+        let stick = gamepad.right_stick();
+        look += vec2(stick.x, -stick.y) * bindings.gamepad_look_speed * time.delta_secs();
+    }
+    if look == Vec2::ZERO {
         return;
     }
 
-    let mouse_delta = mouse_motion.delta;
-    let sensitivity = 8e-3;
-    let mut tf = transform_query.single_mut().unwrap();
-    tf.rotate_y(-sensitivity * mouse_delta.x);
-    tf.rotate_local_x(-sensitivity * mouse_delta.y);
+    let (yaw, pitch, _) = transform_query.rotation.to_euler(EulerRot::YXZ);
+    let yaw = yaw - look.x;
+    let pitch = (pitch - look.y).clamp(-MAX_PITCH, MAX_PITCH);
+    transform_query.rotation = Quat::from_euler(EulerRot::YXZ, yaw, pitch, 0.);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fly_for_a_second(free_fly: &mut FreeFly, dir: Vec3, fps: u32) -> Vec3 {
+        (0..fps)
+            .map(|_| free_fly.step(dir, false, 1. / fps as f32))
+            .sum()
+    }
+
+    #[test]
+    fn speed_doesnt_depend_on_the_frame_rate() {
+        let mut slow = FreeFly::default();
+        let mut fast = FreeFly::default();
+        let slow_moved = fly_for_a_second(&mut slow, Vec3::X, 30);
+        let fast_moved = fly_for_a_second(&mut fast, Vec3::X, 144);
+        assert!(slow.velocity.abs_diff_eq(fast.velocity, 0.01));
+        assert!((slow_moved.x - fast_moved.x).abs() < 2.);
+    }
+
+    #[test]
+    fn speed_stays_under_the_top_speed() {
+        let mut free_fly = FreeFly::default();
+        // diagonals arent faster
+        fly_for_a_second(&mut free_fly, vec3(1., 1., 1.), 60);
+        assert!(free_fly.velocity.length() <= free_fly.speed);
+        free_fly.step(Vec3::X, true, 10.);
+        assert!(free_fly.velocity.length() <= free_fly.speed * 2. + 0.01);
+    }
+
+    #[test]
+    fn camera_slows_down_by_itself() {
+        let mut free_fly = FreeFly::default();
+        fly_for_a_second(&mut free_fly, Vec3::Z, 60);
+        let moving = free_fly.velocity.length();
+        fly_for_a_second(&mut free_fly, Vec3::ZERO, 60);
+        assert!(free_fly.velocity.length() < moving * 0.05);
+    }
 }
@@ -0,0 +1,276 @@
+use crate::{game_mode::GameMode, menu, player, twitch, AssetHandles, GameState};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub struct TeamPlugin;
+
+impl Plugin for TeamPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, change_team.run_if(in_state(GameState::Connected)))
+            .add_systems(
+                OnEnter(GameState::Spectating),
+                // before the roster so respawned goats come back on the new team
+                soccer_teams
+                    .run_if(is_soccer)
+                    .before(player::remember_roster),
+            );
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Team {
+    Red,
+    Blue,
+    Green,
+    Yellow,
+}
+
+impl Team {
+    pub const ALL: [Team; 4] = [Team::Red, Team::Blue, Team::Green, Team::Yellow];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "red" => Some(Team::Red),
+            "blue" => Some(Team::Blue),
+            "green" => Some(Team::Green),
+            "yellow" => Some(Team::Yellow),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Team::Red => "RED",
+            Team::Blue => "BLUE",
+            Team::Green => "GREEN",
+            Team::Yellow => "YELLOW",
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Team::Red => Color::srgb(1., 0.4, 0.4),
+            Team::Blue => Color::srgb(0.4, 0.5, 1.),
+            Team::Green => Color::srgb(0.4, 1., 0.4),
+            Team::Yellow => Color::srgb(1., 0.9, 0.3),
+        }
+    }
+
+    fn random_of_two() -> Self {
+        if rand::random::<bool>() {
+            Team::Red
+        } else {
+            Team::Blue
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum TeamMode {
+    #[default]
+    Off,
+    Random,
+    Chosen,
+    Badge,
+}
+
+impl TeamMode {
+    pub fn next(self) -> Self {
+        match self {
+            TeamMode::Off => TeamMode::Random,
+            TeamMode::Random => TeamMode::Chosen,
+            TeamMode::Chosen => TeamMode::Badge,
+            TeamMode::Badge => TeamMode::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TeamMode::Off => "off",
+            TeamMode::Random => "random",
+            TeamMode::Chosen => "!team <color>",
+            TeamMode::Badge => "subs (yellow) vs rest (blue)",
+        }
+    }
+}
+
+// "!team red" -> red
+pub fn parse_choice(text: &str) -> Option<Team> {
+    Team::parse(text.trim().strip_prefix("!team ")?)
+}
+
+/// picks the team for a new goat, none when teams are off.
+/// soccer only has a red and a blue goal so its always red against blue
+pub fn assign(settings: &menu::Settings, msg: Option<&twitch::Message>) -> Option<Team> {
+    let team = match settings.team_mode {
+        TeamMode::Off => None,
+        TeamMode::Random => Some(Team::random_of_two()),
+        TeamMode::Chosen => Some(
+            msg.and_then(|msg| parse_choice(&msg.text))
+                .unwrap_or_else(Team::random_of_two),
+        ),
+        TeamMode::Badge => {
+            let is_sub = msg.is_some_and(|msg| msg.is_sub());
+            Some(if is_sub { Team::Yellow } else { Team::Blue })
+        }
+    };
+    if settings.game_mode != GameMode::Soccer {
+        return team;
+    }
+    match (settings.team_mode, team) {
+        (_, Some(team @ (Team::Red | Team::Blue))) => Some(team),
+        // subs against the rest still works in soccer colors
+        (TeamMode::Badge, Some(Team::Yellow)) => Some(Team::Red),
+        _ => Some(Team::random_of_two()),
+    }
+}
+
+fn is_soccer(settings: Res<menu::Settings>) -> bool {
+    settings.game_mode == GameMode::Soccer
+}
+
+// goats that joined before soccer was picked or with teams off still need a side with a goal
+fn soccer_teams(
+    mut commands: Commands,
+    asset_handles: Res<AssetHandles>,
+    player_query: Query<Option<&Team>, With<player::Player>>,
+    model_query: Query<(Entity, &ChildOf), With<player::GoatModel>>,
+) {
+    for (model, child_of) in &model_query {
+        let goat = child_of.parent();
+        let Ok(team) = player_query.get(goat) else {
+            continue;
+        };
+        if matches!(team, Some(Team::Red | Team::Blue)) {
+            continue;
+        }
+        let team = Team::random_of_two();
+        commands.entity(goat).insert(team);
+        commands
+            .entity(model)
+            .insert(MeshMaterial3d(asset_handles.team_materials[&team].clone()));
+    }
+}
+
+// lets people who already joined switch teams before the round starts
+fn change_team(
+    mut commands: Commands,
+    mut events: EventReader<twitch::ChatMessage>,
+    settings: Res<menu::Settings>,
+    asset_handles: Res<AssetHandles>,
+    player_query: Query<&player::Player>,
+    model_query: Query<(Entity, &ChildOf), With<player::GoatModel>>,
+) {
+    for event in events.read() {
+        if settings.team_mode != TeamMode::Chosen {
+            continue;
+        }
+        let Some(team) = parse_choice(&event.0.text) else {
+            continue;
+        };
+        // theres no goal for green or yellow
+        if settings.game_mode == GameMode::Soccer && !matches!(team, Team::Red | Team::Blue) {
+            continue;
+        }
+
+        for (model, child_of) in &model_query {
+            let goat = child_of.parent();
+            if !player_query
+                .get(goat)
+                .is_ok_and(|player| player.is_owned_by(&event.0.sender))
+            {
+                continue;
+            }
+            commands.entity(goat).insert(team);
+            commands
+                .entity(model)
+                .insert(MeshMaterial3d(asset_handles.team_materials[&team].clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(text: &str, badges: &[&str]) -> twitch::Message {
+        twitch::Message {
+            sender: "someone".to_owned(),
+            display_name: "SomeOne".to_owned(),
+            text: text.to_owned(),
+            badges: badges.iter().map(|badge| (*badge).to_owned()).collect(),
+            reward: None,
+            bits: 0,
+        }
+    }
+
+    #[test]
+    fn team_choices_parse() {
+        assert_eq!(parse_choice("!team red"), Some(Team::Red));
+        assert_eq!(parse_choice("  !team   YELLOW "), Some(Team::Yellow));
+        assert_eq!(parse_choice("!team purple"), None);
+        assert_eq!(parse_choice("!team"), None);
+        assert_eq!(parse_choice("team red"), None);
+        assert_eq!(parse_choice("!play"), None);
+    }
+
+    #[test]
+    fn chosen_teams_are_kept() {
+        let settings = menu::Settings {
+            team_mode: TeamMode::Chosen,
+            ..Default::default()
+        };
+        let msg = message("!team green", &[]);
+        assert_eq!(assign(&settings, Some(&msg)), Some(Team::Green));
+        // no choice still gets a team
+        assert!(assign(&settings, None).is_some());
+    }
+
+    #[test]
+    fn badges_pick_the_team() {
+        let settings = menu::Settings {
+            team_mode: TeamMode::Badge,
+            ..Default::default()
+        };
+        let sub = message("!play", &["subscriber"]);
+        let founder = message("!play", &["founder"]);
+        let viewer = message("!play", &["vip"]);
+        assert_eq!(assign(&settings, Some(&sub)), Some(Team::Yellow));
+        assert_eq!(assign(&settings, Some(&founder)), Some(Team::Yellow));
+        assert_eq!(assign(&settings, Some(&viewer)), Some(Team::Blue));
+        assert_eq!(assign(&settings, None), Some(Team::Blue));
+    }
+
+    #[test]
+    fn no_teams_when_off() {
+        let settings = menu::Settings::default();
+        let msg = message("!team red", &[]);
+        assert_eq!(assign(&settings, Some(&msg)), None);
+    }
+
+    #[test]
+    fn soccer_is_always_red_against_blue() {
+        let mut settings = menu::Settings {
+            team_mode: TeamMode::Chosen,
+            game_mode: GameMode::Soccer,
+            ..Default::default()
+        };
+        let green = message("!team green", &[]);
+        assert!(matches!(
+            assign(&settings, Some(&green)),
+            Some(Team::Red | Team::Blue)
+        ));
+        let blue = message("!team blue", &[]);
+        assert_eq!(assign(&settings, Some(&blue)), Some(Team::Blue));
+
+        settings.team_mode = TeamMode::Badge;
+        let sub = message("!play", &["subscriber"]);
+        assert_eq!(assign(&settings, Some(&sub)), Some(Team::Red));
+
+        settings.team_mode = TeamMode::Off;
+        assert!(matches!(
+            assign(&settings, None),
+            Some(Team::Red | Team::Blue)
+        ));
+    }
+}
@@ -0,0 +1,533 @@
+use bevy::color::palettes::basic;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy::window::Ime;
+
+// how fast the cursor blinks, in blinks per second
+const BLINK_RATE: f32 = 2.;
+
+pub struct TextInputPlugin;
+
+impl Plugin for TextInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TextFocus::default())
+            .add_event::<TextSubmitted>()
+            .add_systems(
+                Update,
+                (
+                    autofocus,
+                    focus_on_click,
+                    type_text,
+                    paste_ime_text,
+                    update_ime,
+                    update_input_text,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// what a text field accepts
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TextRule {
+    #[default]
+    Any,
+    // twitch logins, lowercase letters, digits and underscores
+    ChannelName,
+}
+
+impl TextRule {
+    // turns a typed char into what goes in the field, none if it cant go in at all
+    fn accept(self, c: char) -> Option<char> {
+        if c.is_control() {
+            return None;
+        }
+        match self {
+            TextRule::Any => Some(c),
+            TextRule::ChannelName => {
+                let c = c.to_ascii_lowercase();
+                (c.is_ascii_alphanumeric() || c == '_').then_some(c)
+            }
+        }
+    }
+
+    fn max_len(self) -> usize {
+        match self {
+            TextRule::Any => 200,
+            TextRule::ChannelName => 25,
+        }
+    }
+
+    /// why the text isnt allowed yet, if it isnt
+    pub fn check(self, text: &str) -> Result<(), &'static str> {
+        match self {
+            TextRule::Any => Ok(()),
+            // new names need 4 chars but there are a few older 3 char channels
+            TextRule::ChannelName if text.chars().count() < 3 => Err("too short"),
+            TextRule::ChannelName if text.starts_with('_') => Err("cant start with _"),
+            TextRule::ChannelName => Ok(()),
+        }
+    }
+}
+
+/// a one line text field, spawn it with text_input
+#[derive(Component, Default, Debug)]
+#[require(Button)]
+pub struct TextInput {
+    text: String,
+    // byte positions in text, the selection goes from anchor to cursor
+    cursor: usize,
+    anchor: Option<usize>,
+    pub rule: TextRule,
+    pub placeholder: String,
+}
+
+impl TextInput {
+    pub fn new(text: &str, rule: TextRule) -> Self {
+        let mut input = TextInput {
+            rule,
+            ..Default::default()
+        };
+        input.set_text(text);
+        input
+    }
+
+    pub fn with_placeholder(mut self, placeholder: impl Into<String>) -> Self {
+        self.placeholder = placeholder.into();
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.text.clear();
+        self.cursor = 0;
+        self.anchor = None;
+        self.insert(text);
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.rule.check(&self.text).is_ok()
+    }
+
+    fn selection(&self) -> Option<(usize, usize)> {
+        let anchor = self.anchor.filter(|anchor| *anchor != self.cursor)?;
+        Some((anchor.min(self.cursor), anchor.max(self.cursor)))
+    }
+
+    fn selected_text(&self) -> Option<&str> {
+        self.selection().map(|(start, end)| &self.text[start..end])
+    }
+
+    fn delete_selection(&mut self) -> bool {
+        let Some((start, end)) = self.selection() else {
+            self.anchor = None;
+            return false;
+        };
+        self.text.replace_range(start..end, "");
+        self.cursor = start;
+        self.anchor = None;
+        true
+    }
+
+    /// types text at the cursor, dropping whatever the rule doesnt allow
+    pub fn insert(&mut self, text: &str) {
+        self.delete_selection();
+        for c in text.chars().filter_map(|c| self.rule.accept(c)) {
+            if self.text.chars().count() >= self.rule.max_len() {
+                break;
+            }
+            self.text.insert(self.cursor, c);
+            self.cursor += c.len_utf8();
+        }
+    }
+
+    fn prev_boundary(&self, pos: usize) -> usize {
+        self.text[..pos]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self, pos: usize) -> usize {
+        self.text[pos..]
+            .chars()
+            .next()
+            .map_or(pos, |c| pos + c.len_utf8())
+    }
+
+    fn backspace(&mut self) {
+        if !self.delete_selection() && self.cursor > 0 {
+            let start = self.prev_boundary(self.cursor);
+            self.text.replace_range(start..self.cursor, "");
+            self.cursor = start;
+        }
+    }
+
+    fn delete(&mut self) {
+        if !self.delete_selection() {
+            let end = self.next_boundary(self.cursor);
+            self.text.replace_range(self.cursor..end, "");
+        }
+    }
+
+    // moves the cursor, holding shift grows the selection instead of dropping it
+    fn move_to(&mut self, pos: usize, select: bool) {
+        if select {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = pos;
+    }
+
+    fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.text.len();
+    }
+}
+
+/// sent when enter is pressed in a text field
+#[derive(Event)]
+pub struct TextSubmitted {
+    pub input: Entity,
+    pub text: String,
+}
+
+/// the text field that gets the keyboard, if any
+#[derive(Resource, Default)]
+pub struct TextFocus(pub Option<Entity>);
+
+pub fn not_typing(focus: Res<TextFocus>) -> bool {
+    focus.0.is_none()
+}
+
+/// focuses the text field as soon as its spawned
+#[derive(Component)]
+pub struct AutoFocus;
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum InputPart {
+    Before,
+    Selected,
+    Cursor,
+    After,
+    Error,
+}
+
+pub fn text_input(input: TextInput, width: Val, font_size: f32) -> impl Bundle {
+    let span = |part: InputPart| {
+        (
+            part,
+            TextSpan::default(),
+            TextFont::default().with_font_size(font_size),
+            TextColor::default(),
+        )
+    };
+    (
+        input,
+        Node {
+            width,
+            height: Val::Px(font_size * 2.),
+            align_items: AlignItems::Center,
+            padding: UiRect::horizontal(Val::Px(10.)),
+            border: UiRect::all(Val::Px(3.)),
+            overflow: Overflow::clip_x(),
+            ..Default::default()
+        },
+        BorderColor(basic::GRAY.into()),
+        BorderRadius::all(Val::Px(8.)),
+        BackgroundColor(Color::BLACK.with_alpha(0.5)),
+        children![
+            (
+                Text::default(),
+                TextFont::default().with_font_size(font_size),
+                children![
+                    span(InputPart::Before),
+                    span(InputPart::Selected),
+                    span(InputPart::Cursor),
+                    span(InputPart::After),
+                ],
+            ),
+            (
+                InputPart::Error,
+                Text::default(),
+                TextFont::default().with_font_size(font_size * 0.6),
+                TextColor(basic::RED.into()),
+                Node {
+                    position_type: PositionType::Absolute,
+                    top: Val::Percent(100.),
+                    left: Val::Px(10.),
+                    ..Default::default()
+                },
+            ),
+        ],
+    )
+}
+
+fn autofocus(
+    mut commands: Commands,
+    mut focus: ResMut<TextFocus>,
+    input_query: Query<Entity, (With<TextInput>, Added<AutoFocus>)>,
+) {
+    for input in &input_query {
+        focus.0 = Some(input);
+        commands.entity(input).remove::<AutoFocus>();
+    }
+}
+
+// clicking a field focuses it, clicking anywhere else lets go of it
+fn focus_on_click(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    mut focus: ResMut<TextFocus>,
+    mut input_query: Query<(Entity, &Interaction, &mut TextInput)>,
+) {
+    if focus.0.is_some_and(|input| !input_query.contains(input)) {
+        focus.0 = None;
+    }
+    if !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+    focus.0 = None;
+    for (entity, interaction, mut input) in &mut input_query {
+        if *interaction == Interaction::Pressed {
+            focus.0 = Some(entity);
+            let end = input.text.len();
+            input.move_to(end, false);
+        }
+    }
+}
+
+fn type_text(
+    mut keyboard_events: EventReader<KeyboardInput>,
+    input: Res<ButtonInput<KeyCode>>,
+    mut focus: ResMut<TextFocus>,
+    mut input_query: Query<&mut TextInput>,
+    mut submit_events: EventWriter<TextSubmitted>,
+) {
+    let Some(mut text_input) = focus.0.and_then(|input| input_query.get_mut(input).ok()) else {
+        keyboard_events.clear();
+        return;
+    };
+    let shift = input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    // cmd on mac
+    let ctrl = input.any_pressed([
+        KeyCode::ControlLeft,
+        KeyCode::ControlRight,
+        KeyCode::SuperLeft,
+        KeyCode::SuperRight,
+    ]);
+
+    for event in keyboard_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        match &event.logical_key {
+            Key::Character(c) if ctrl => match c.to_lowercase().as_str() {
+                "a" => text_input.select_all(),
+                "c" => copy(text_input.selected_text()),
+                "x" => {
+                    copy(text_input.selected_text());
+                    text_input.delete_selection();
+                }
+                "v" => {
+                    if let Some(text) = paste() {
+                        text_input.insert(text.trim());
+                    }
+                }
+                _ => {}
+            },
+            Key::Backspace => text_input.backspace(),
+            Key::Delete => text_input.delete(),
+            Key::ArrowLeft => {
+                let pos = match text_input.selection() {
+                    Some((start, _)) if !shift => start,
+                    _ => text_input.prev_boundary(text_input.cursor),
+                };
+                text_input.move_to(pos, shift);
+            }
+            Key::ArrowRight => {
+                let pos = match text_input.selection() {
+                    Some((_, end)) if !shift => end,
+                    _ => text_input.next_boundary(text_input.cursor),
+                };
+                text_input.move_to(pos, shift);
+            }
+            Key::Home => text_input.move_to(0, shift),
+            Key::End => {
+                let end = text_input.text.len();
+                text_input.move_to(end, shift);
+            }
+            Key::Enter => {
+                submit_events.write(TextSubmitted {
+                    input: focus.0.unwrap(),
+                    text: text_input.text.clone(),
+                });
+            }
+            Key::Escape => {
+                focus.0 = None;
+                return;
+            }
+            // the text is whatever the keyboard layout makes of the key, so this works on any layout.
+            // its none while an ime is composing, that text comes in paste_ime_text instead
+            _ => {
+                if let Some(text) = &event.text {
+                    text_input.insert(text);
+                }
+            }
+        }
+    }
+}
+
+fn paste_ime_text(
+    mut ime_events: EventReader<Ime>,
+    focus: Res<TextFocus>,
+    mut input_query: Query<&mut TextInput>,
+) {
+    for event in ime_events.read() {
+        let Ime::Commit { value, .. } = event else {
+            continue;
+        };
+        if let Some(mut text_input) = focus.0.and_then(|input| input_query.get_mut(input).ok()) {
+            text_input.insert(value);
+        }
+    }
+}
+
+// turns the ime on only while typing, so it doesnt eat the game controls
+fn update_ime(
+    focus: Res<TextFocus>,
+    mut window: Single<&mut Window>,
+    input_query: Query<&GlobalTransform, With<TextInput>>,
+) {
+    let focused = focus.0.and_then(|input| input_query.get(input).ok());
+    if window.ime_enabled != focused.is_some() {
+        window.ime_enabled = focused.is_some();
+    }
+    if let Some(trans) = focused {
+        window.ime_position = trans.translation().truncate();
+    }
+}
+
+fn update_input_text(
+    time: Res<Time<Real>>,
+    focus: Res<TextFocus>,
+    mut input_query: Query<(Entity, &TextInput, &mut BorderColor)>,
+    children_query: Query<&Children>,
+    mut part_query: Query<(
+        &InputPart,
+        Option<&mut TextSpan>,
+        Option<&mut Text>,
+        &mut TextColor,
+    )>,
+) {
+    let blink_on = (time.elapsed_secs() * BLINK_RATE) as u32 % 2 == 0;
+
+    for (entity, input, mut border) in &mut input_query {
+        let focused = focus.0 == Some(entity);
+        border.0 = if focused {
+            Color::WHITE
+        } else {
+            basic::GRAY.into()
+        };
+
+        let (start, end) = input.selection().unwrap_or((input.cursor, input.cursor));
+        let error = match input.rule.check(&input.text) {
+            Err(error) if !input.text.is_empty() => error,
+            _ => "",
+        };
+
+        for part in children_query.iter_descendants(entity) {
+            let Ok((part, span, text, mut color)) = part_query.get_mut(part) else {
+                continue;
+            };
+            let (value, new_color) = match part {
+                InputPart::Before if input.text.is_empty() && !focused => {
+                    (input.placeholder.as_str(), basic::GRAY.into())
+                }
+                InputPart::Before => (&input.text[..start], Color::WHITE),
+                InputPart::Selected => (&input.text[start..end], basic::AQUA.into()),
+                InputPart::Cursor if focused && blink_on => ("|", Color::WHITE),
+                InputPart::Cursor => ("", Color::WHITE),
+                InputPart::After => (&input.text[end..], Color::WHITE),
+                InputPart::Error => (error, basic::RED.into()),
+            };
+            if color.0 != new_color {
+                color.0 = new_color;
+            }
+            if let Some(mut span) = span.filter(|span| span.0 != value) {
+                span.0 = value.to_owned();
+            }
+            if let Some(mut text) = text.filter(|text| text.0 != value) {
+                text.0 = value.to_owned();
+            }
+        }
+    }
+}
+
+fn copy(text: Option<&str>) {
+    let Some(text) = text else {
+        return;
+    };
+    if let Err(err) = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text)) {
+        error!("couldnt copy: {err}");
+    }
+}
+
+fn paste() -> Option<String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .inspect_err(|err| error!("couldnt paste: {err}"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_names_are_checked() {
+        let rule = TextRule::ChannelName;
+        assert_eq!(rule.check("ab"), Err("too short"));
+        assert_eq!(rule.check("_goat"), Err("cant start with _"));
+        assert_eq!(rule.check("abc"), Ok(()));
+        assert_eq!(rule.check("goat_stream_99"), Ok(()));
+        assert_eq!(TextRule::Any.check(""), Ok(()));
+    }
+
+    #[test]
+    fn channel_names_drop_what_twitch_doesnt_allow() {
+        let input = TextInput::new(" Goat Stream!\n", TextRule::ChannelName);
+        assert_eq!(input.text(), "goatstream");
+        let input = TextInput::new(&"a".repeat(40), TextRule::ChannelName);
+        assert_eq!(input.text().len(), 25);
+    }
+
+    #[test]
+    fn typing_replaces_the_selection() {
+        let mut input = TextInput::new("hello goat", TextRule::Any);
+        input.move_to(6, false);
+        input.move_to(10, true);
+        assert_eq!(input.selected_text(), Some("goat"));
+        input.insert("sheep");
+        assert_eq!(input.text(), "hello sheep");
+        input.select_all();
+        input.backspace();
+        assert_eq!(input.text(), "");
+    }
+
+    #[test]
+    fn editing_keeps_to_char_boundaries() {
+        let mut input = TextInput::new("gö🐐t", TextRule::Any);
+        input.backspace();
+        assert_eq!(input.text(), "gö🐐");
+        input.backspace();
+        assert_eq!(input.text(), "gö");
+        input.move_to(input.prev_boundary(input.cursor), false);
+        input.delete();
+        assert_eq!(input.text(), "g");
+    }
+}
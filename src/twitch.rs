@@ -4,13 +4,38 @@ use std::sync::Mutex;
 
 #[derive(Debug, Clone)]
 pub struct Message {
+    // the twitch login, always lowercase, only run through the name filter where its shown
     pub sender: String,
+    // how the viewer writes their name, can have capitals
+    pub display_name: String,
     pub text: String,
+    pub badges: Vec<String>,
+    // the custom-reward-id tag, only there for channel point rewards that ask for text
+    pub reward: Option<String>,
+    // 0 unless its a cheer
+    pub bits: u64,
+}
+
+impl Message {
+    pub fn is_sub(&self) -> bool {
+        self.badges
+            .iter()
+            .any(|badge| badge == "subscriber" || badge == "founder")
+    }
+
+    pub fn is_mod(&self) -> bool {
+        self.badges
+            .iter()
+            .any(|badge| badge == "moderator" || badge == "broadcaster")
+    }
 }
 
 #[derive(Event)]
 pub struct UserJoined(pub Message);
 
+#[derive(Event)]
+pub struct ChatMessage(pub Message);
+
 #[derive(Event)]
 pub struct ConnectEvent(pub String);
 
@@ -21,6 +46,7 @@ impl Plugin for TwitchPlugin {
         app.add_plugins(bevy_tokio_tasks::TokioTasksPlugin::default())
             .add_systems(Update, connect)
             .add_event::<UserJoined>()
+            .add_event::<ChatMessage>()
             .add_event::<ConnectEvent>();
     }
 }
@@ -65,10 +91,22 @@ async fn start_listening(mut ctx: bevy_tokio_tasks::TaskContext, name: String) {
         ctx.run_on_main_thread(move |ctx| {
             while let Ok(message) = receiver.lock().unwrap().try_recv() {
                 if let twitch_irc::message::ServerMessage::Privmsg(msg) = message {
-                    ctx.world.send_event(UserJoined(Message {
-                        sender: msg.sender.name,
+                    let message = Message {
+                        sender: msg.sender.login,
+                        display_name: msg.sender.name,
                         text: msg.message_text,
-                    }));
+                        badges: msg.badges.into_iter().map(|badge| badge.name).collect(),
+                        reward: msg
+                            .source
+                            .tags
+                            .0
+                            .get("custom-reward-id")
+                            .filter(|id| !id.is_empty())
+                            .cloned(),
+                        bits: msg.bits.unwrap_or(0),
+                    };
+                    ctx.world.send_event(ChatMessage(message.clone()));
+                    ctx.world.send_event(UserJoined(message));
                 }
             }
         })
@@ -0,0 +1,501 @@
+use crate::{feed, game_mode, menu, moderation, player, respawn::RespawnMode, twitch, GameState};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+const STATS_FILE: &str = "viewer_stats.json";
+pub const EXPORT_FILE: &str = "viewer_stats.csv";
+// viewers on the end screen leaderboard
+const LEADERBOARD_SIZE: usize = 10;
+// so one viewer cant flood the feed with !stats
+const STATS_COOLDOWN_SECS: f32 = 30.;
+
+pub struct ViewerStatsPlugin;
+
+impl Plugin for ViewerStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(AllTimeStats::load(Path::new(STATS_FILE)))
+            .insert_resource(RoundTracker::default())
+            .add_systems(OnEnter(GameState::Spectating), reset_tracker)
+            .add_systems(
+                Update,
+                (
+                    track_round.run_if(in_state(GameState::Spectating)),
+                    answer_stats_command,
+                ),
+            )
+            .add_systems(
+                OnEnter(GameState::End),
+                (record_round, setup_leaderboard).chain(),
+            )
+            .add_systems(OnExit(GameState::End), despawn_leaderboard);
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct ViewerStats {
+    pub rounds: u32,
+    pub wins: u32,
+    pub knockouts: u32,
+    // 1 is first place
+    pub best_placement: Option<u32>,
+    pub survival_secs: f32,
+    pub longest_survival_secs: f32,
+}
+
+impl ViewerStats {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} rounds, {} wins, {} knockouts, best place {}, survived {:.0}s in total",
+            self.rounds,
+            self.wins,
+            self.knockouts,
+            self.best_placement
+                .map_or("-".to_owned(), |place| format!("#{place}")),
+            self.survival_secs
+        )
+    }
+}
+
+/// stats of every viewer that ever played, kept in viewer_stats.json
+#[derive(Resource, Serialize, Deserialize, Default)]
+pub struct AllTimeStats(pub BTreeMap<String, ViewerStats>);
+
+impl AllTimeStats {
+    pub fn load(path: &Path) -> Self {
+        let Ok(text) = std::fs::read_to_string(path) else {
+            return AllTimeStats::default();
+        };
+        serde_json::from_str(&text).unwrap_or_else(|err| {
+            error!("couldnt read {}: {err}", path.display());
+            AllTimeStats::default()
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn export_csv(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_csv())
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv =
+            "viewer,rounds,wins,knockouts,best_placement,survival_secs,longest_survival_secs\n"
+                .to_owned();
+        for (viewer, stats) in &self.0 {
+            csv += &format!(
+                "{},{},{},{},{},{:.1},{:.1}\n",
+                csv_field(viewer),
+                stats.rounds,
+                stats.wins,
+                stats.knockouts,
+                stats
+                    .best_placement
+                    .map_or(String::new(), |place| place.to_string()),
+                stats.survival_secs,
+                stats.longest_survival_secs
+            );
+        }
+        csv
+    }
+
+    // most wins first, knockouts break ties
+    pub fn leaderboard(&self, count: usize) -> Vec<(&String, &ViewerStats)> {
+        let mut viewers = self.0.iter().collect::<Vec<_>>();
+        viewers.sort_by(|a, b| {
+            b.1.wins
+                .cmp(&a.1.wins)
+                .then(b.1.knockouts.cmp(&a.1.knockouts))
+        });
+        viewers.truncate(count);
+        viewers
+    }
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
+
+#[derive(Default)]
+struct RoundEntry {
+    knockouts: u32,
+    best_placement: Option<u32>,
+}
+
+// what happened to each viewer in the current round
+#[derive(Resource, Default)]
+struct RoundTracker {
+    viewers: HashMap<String, RoundEntry>,
+    // every goat in the round including bots, for placements
+    goats: HashSet<String>,
+    bots: HashSet<String>,
+    deaths: u32,
+    // with respawns a death doesnt knock anyone out, so theres no placements
+    placements: bool,
+    // when the current life of each goat started, and how long its earlier lives lasted
+    alive_since: HashMap<String, f32>,
+    alive_secs: HashMap<String, f32>,
+}
+
+impl RoundTracker {
+    fn record_death(&mut self, goat: &str, killer: Option<&str>, now: f32) {
+        // the first goat out is last place
+        let placement = (self.goats.len() as u32).saturating_sub(self.deaths).max(1);
+        self.deaths += 1;
+        self.end_life(goat, now);
+
+        // bots dropped in by a redemption are named after the viewer but arent theirs
+        if let Some(killer) = killer.filter(|killer| !self.bots.contains(*killer)) {
+            self.viewers
+                .entry(player::viewer_of(killer).to_owned())
+                .or_default()
+                .knockouts += 1;
+        }
+        if !self.placements || self.bots.contains(goat) {
+            return;
+        }
+        let entry = self
+            .viewers
+            .entry(player::viewer_of(goat).to_owned())
+            .or_default();
+        entry.best_placement = Some(
+            entry
+                .best_placement
+                .map_or(placement, |best| best.min(placement)),
+        );
+    }
+
+    fn end_life(&mut self, goat: &str, now: f32) {
+        if let Some(since) = self.alive_since.remove(goat) {
+            *self.alive_secs.entry(goat.to_owned()).or_default() += now - since;
+        }
+    }
+
+    // the longest any goat of the viewer was alive, adding up its lives
+    fn survival_secs(&self, viewer: &str) -> f32 {
+        self.alive_secs
+            .iter()
+            .filter(|(goat, _)| !self.bots.contains(*goat) && player::is_owned_by(goat, viewer))
+            .map(|(_, secs)| *secs)
+            .fold(0., f32::max)
+    }
+}
+
+#[derive(Component)]
+struct Leaderboard;
+
+fn reset_tracker(settings: Res<menu::Settings>, mut tracker: ResMut<RoundTracker>) {
+    *tracker = RoundTracker {
+        placements: settings.respawn == RespawnMode::Off,
+        ..Default::default()
+    };
+}
+
+fn track_round(
+    round_time: Res<game_mode::RoundTime>,
+    mut death_events: EventReader<player::GoatDied>,
+    mut tracker: ResMut<RoundTracker>,
+    player_query: Query<(&player::Player, Has<player::Bot>)>,
+    added_query: Query<&player::Player, Added<player::Player>>,
+) {
+    // respawned goats are new entities, so every life starts here
+    for player in &added_query {
+        tracker
+            .alive_since
+            .insert(player.name().to_owned(), round_time.0);
+    }
+    for (player, is_bot) in &player_query {
+        if tracker.goats.contains(player.name()) {
+            continue;
+        }
+        tracker.goats.insert(player.name().to_owned());
+        if is_bot {
+            tracker.bots.insert(player.name().to_owned());
+        } else {
+            tracker
+                .viewers
+                .entry(player.viewer().to_owned())
+                .or_default();
+        }
+    }
+
+    for event in death_events.read() {
+        if event.goat.bot {
+            tracker.bots.insert(event.name.clone());
+        }
+        tracker.record_death(&event.name, event.killer.as_deref(), round_time.0);
+    }
+}
+
+fn record_round(
+    round_time: Res<game_mode::RoundTime>,
+    round_result: Res<game_mode::RoundResult>,
+    mut tracker: ResMut<RoundTracker>,
+    mut all_time: ResMut<AllTimeStats>,
+    player_query: Query<&player::Player, Without<player::Bot>>,
+) {
+    let alive = tracker.alive_since.keys().cloned().collect::<Vec<_>>();
+    for goat in alive {
+        tracker.end_life(&goat, round_time.0);
+    }
+    let winners = round_result
+        .winners
+        .iter()
+        .filter(|name| !tracker.bots.contains(*name))
+        .map(|name| player::viewer_of(name).to_owned())
+        .collect::<HashSet<_>>();
+    // the winners are first, everyone else still alive shares the best place nobody was knocked out of
+    let still_in = (tracker.goats.len() as u32)
+        .saturating_sub(tracker.deaths)
+        .max(1);
+    if tracker.placements {
+        for player in &player_query {
+            let placement = if winners.contains(player.viewer()) {
+                1
+            } else {
+                still_in
+            };
+            let entry = tracker
+                .viewers
+                .entry(player.viewer().to_owned())
+                .or_default();
+            entry.best_placement = Some(
+                entry
+                    .best_placement
+                    .map_or(placement, |best| best.min(placement)),
+            );
+        }
+    }
+
+    let viewers = std::mem::take(&mut tracker.viewers);
+    for (viewer, entry) in viewers {
+        let stats = all_time.0.entry(viewer.clone()).or_default();
+        stats.rounds += 1;
+        stats.knockouts += entry.knockouts;
+        if winners.contains(&viewer) {
+            stats.wins += 1;
+        }
+        if let Some(placement) = entry.best_placement {
+            stats.best_placement = Some(
+                stats
+                    .best_placement
+                    .map_or(placement, |best| best.min(placement)),
+            );
+        }
+        let survived = tracker.survival_secs(&viewer);
+        stats.survival_secs += survived;
+        stats.longest_survival_secs = stats.longest_survival_secs.max(survived);
+    }
+
+    if let Err(err) = all_time.save(Path::new(STATS_FILE)) {
+        error!("couldnt save viewer stats: {err}");
+    }
+}
+
+fn setup_leaderboard(
+    mut commands: Commands,
+    all_time: Res<AllTimeStats>,
+    name_filter: Res<moderation::NameFilter>,
+) {
+    let mut lines = vec!["ALL TIME".to_owned()];
+    for (i, (viewer, stats)) in all_time
+        .leaderboard(LEADERBOARD_SIZE)
+        .into_iter()
+        .enumerate()
+    {
+        lines.push(format!(
+            "{}. {}  {} wins  {} ko  {} rounds",
+            i + 1,
+            name_filter.display(viewer),
+            stats.wins,
+            stats.knockouts,
+            stats.rounds
+        ));
+    }
+
+    commands.spawn((
+        Leaderboard,
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Percent(25.),
+            right: Val::Percent(2.),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::FlexEnd,
+            row_gap: Val::Px(10.),
+            padding: UiRect::all(Val::Px(10.)),
+            ..Default::default()
+        },
+        BackgroundColor(Color::BLACK.with_alpha(0.4)),
+        BorderRadius::all(Val::Px(8.)),
+        children![
+            (
+                Text::new(lines.join("\n")),
+                TextFont::default().with_font_size(24.),
+            ),
+            menu::button(menu::ButtonAction::ExportStats, "EXPORT CSV", Val::Px(250.)),
+        ],
+    ));
+}
+
+fn despawn_leaderboard(
+    mut commands: Commands,
+    leaderboard_query: Query<Entity, With<Leaderboard>>,
+) {
+    for leaderboard in &leaderboard_query {
+        commands.entity(leaderboard).despawn();
+    }
+}
+
+fn answer_stats_command(
+    time: Res<Time<Real>>,
+    all_time: Res<AllTimeStats>,
+    mut chat_events: EventReader<twitch::ChatMessage>,
+    mut feed_events: EventWriter<feed::FeedEvent>,
+    mut last_asked: Local<HashMap<String, f32>>,
+) {
+    let now = time.elapsed_secs();
+    for event in chat_events.read() {
+        if event.0.text.trim() != "!stats" {
+            continue;
+        }
+        let viewer = &event.0.sender;
+        if last_asked
+            .get(viewer)
+            .is_some_and(|asked| now - asked < STATS_COOLDOWN_SECS)
+        {
+            continue;
+        }
+        last_asked.insert(viewer.clone(), now);
+
+        let summary = match all_time.0.get(viewer) {
+            Some(stats) => stats.summary(),
+            None => "hasnt played yet".to_owned(),
+        };
+        feed_events.write(feed::FeedEvent::Stats {
+            name: viewer.clone(),
+            summary,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(wins: u32, knockouts: u32) -> ViewerStats {
+        ViewerStats {
+            rounds: 3,
+            wins,
+            knockouts,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("someone"), "someone");
+        assert_eq!(csv_field("some,one"), "\"some,one\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn csv_has_a_row_per_viewer() {
+        let mut all_time = AllTimeStats::default();
+        all_time.0.insert(
+            "someone".to_owned(),
+            ViewerStats {
+                rounds: 2,
+                wins: 1,
+                knockouts: 4,
+                best_placement: Some(1),
+                survival_secs: 90.,
+                longest_survival_secs: 60.5,
+            },
+        );
+        all_time.0.insert("a,b".to_owned(), ViewerStats::default());
+        assert_eq!(
+            all_time.to_csv(),
+            "viewer,rounds,wins,knockouts,best_placement,survival_secs,longest_survival_secs\n\
+             \"a,b\",0,0,0,,0.0,0.0\n\
+             someone,2,1,4,1,90.0,60.5\n"
+        );
+    }
+
+    #[test]
+    fn leaderboard_goes_by_wins_then_knockouts() {
+        let mut all_time = AllTimeStats::default();
+        all_time.0.insert("few".to_owned(), stats(1, 0));
+        all_time.0.insert("most".to_owned(), stats(5, 0));
+        all_time.0.insert("tied_low".to_owned(), stats(2, 1));
+        all_time.0.insert("tied_high".to_owned(), stats(2, 7));
+        let names = |count| {
+            all_time
+                .leaderboard(count)
+                .into_iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(10), ["most", "tied_high", "tied_low", "few"]);
+        assert_eq!(names(2), ["most", "tied_high"]);
+    }
+
+    #[test]
+    fn survival_adds_up_lives_of_one_goat() {
+        let mut tracker = RoundTracker::default();
+        tracker.alive_since.insert("someone".to_owned(), 0.);
+        tracker.end_life("someone", 10.);
+        // dead for 20 seconds before coming back
+        tracker.alive_since.insert("someone".to_owned(), 30.);
+        tracker.end_life("someone", 35.);
+        tracker.alive_since.insert("someone 2".to_owned(), 0.);
+        tracker.end_life("someone 2", 12.);
+        assert_eq!(tracker.survival_secs("someone"), 15.);
+
+        tracker.bots.insert("someone 3".to_owned());
+        tracker.alive_since.insert("someone 3".to_owned(), 0.);
+        tracker.end_life("someone 3", 100.);
+        assert_eq!(tracker.survival_secs("someone"), 15.);
+    }
+
+    #[test]
+    fn redeemed_bots_dont_score_for_the_viewer() {
+        let mut tracker = RoundTracker {
+            placements: true,
+            ..Default::default()
+        };
+        for goat in ["someone", "other", "someone's goat 2"] {
+            tracker.goats.insert(goat.to_owned());
+        }
+        tracker.bots.insert("someone's goat 2".to_owned());
+
+        tracker.record_death("other", Some("someone's goat 2"), 5.);
+        tracker.record_death("someone's goat 2", Some("someone"), 6.);
+        assert_eq!(tracker.viewers.len(), 2);
+        assert_eq!(tracker.viewers["someone"].knockouts, 1);
+        assert_eq!(tracker.viewers["other"].knockouts, 0);
+        assert_eq!(tracker.viewers["other"].best_placement, Some(3));
+    }
+
+    #[test]
+    fn nobody_is_placed_when_goats_respawn() {
+        let mut tracker = RoundTracker::default();
+        tracker.goats.insert("someone".to_owned());
+        tracker.record_death("someone", None, 5.);
+        tracker.record_death("someone", None, 9.);
+        assert_eq!(
+            tracker
+                .viewers
+                .get("someone")
+                .map(|entry| entry.best_placement),
+            None
+        );
+    }
+}